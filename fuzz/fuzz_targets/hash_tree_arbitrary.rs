@@ -0,0 +1,13 @@
+#![no_main]
+
+use ghashy_collections::hash_tree::*;
+use libfuzzer_sys::fuzz_target;
+
+// Every tree, built from arbitrary input, must be well formed and contain
+// exactly the keys it reports.
+fuzz_target!(|tree: HashTree<u16, u16>| {
+    assert_eq!(tree.validate(), Ok(()));
+    for (key, value) in &tree {
+        assert_eq!(tree.get(key), Some(value));
+    }
+});
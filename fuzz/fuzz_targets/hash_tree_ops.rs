@@ -0,0 +1,79 @@
+#![no_main]
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+
+use arbitrary::Arbitrary;
+use ghashy_collections::hash_tree::*;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+enum Op {
+    Insert(u16, u16),
+    Remove(u16),
+    Get(u16),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    seed: u64,
+    /// Mask applied to every hash, few bits force a lot of collisions.
+    mask: u64,
+    ops: Vec<Op>,
+}
+
+#[derive(Clone)]
+struct MaskedState {
+    state: ahash::RandomState,
+    mask: u64,
+}
+
+struct MaskedHasher {
+    hasher: ahash::AHasher,
+    mask: u64,
+}
+
+impl Hasher for MaskedHasher {
+    fn finish(&self) -> u64 {
+        self.hasher.finish() & self.mask
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes)
+    }
+}
+
+impl BuildHasher for MaskedState {
+    type Hasher = MaskedHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        MaskedHasher {
+            hasher: self.state.build_hasher(),
+            mask: self.mask,
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let seed = input.seed;
+    let mut tree = HashTree::with_hasher(MaskedState {
+        state: ahash::RandomState::with_seeds(seed, seed, seed, seed),
+        mask: input.mask,
+    });
+    let mut model = HashMap::new();
+
+    for op in input.ops {
+        match op {
+            Op::Insert(key, value) => {
+                assert_eq!(tree.insert(key, value), model.insert(key, value))
+            }
+            Op::Remove(key) => {
+                assert_eq!(tree.remove(&key), model.remove(&key))
+            }
+            Op::Get(key) => assert_eq!(tree.get(&key), model.get(&key)),
+        }
+    }
+
+    assert_eq!(tree.validate(), Ok(()));
+    assert_eq!((&tree).into_iter().count(), model.len());
+});
@@ -0,0 +1,172 @@
+#![deny(
+    warnings,
+    missing_copy_implementations,
+    trivial_casts,
+    trivial_numeric_casts,
+    unstable_features,
+    unsafe_code,
+    unused_import_braces,
+    unused_qualifications,
+    missing_docs
+)]
+
+use core::alloc::Layout;
+
+// ───── InvariantError ───────────────────────────────────────────────────── //
+
+/// Structural problem, found by `HashTree::validate` or
+/// `AugmentedHashTree::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantError {
+    /// In-order traversal met `hash` right after a greater `previous` hash,
+    /// so binary search by hash is broken.
+    UnorderedHash {
+        /// Hash of the previous node in in-order traversal.
+        previous: u64,
+        /// Hash of the misplaced node.
+        hash: u64,
+    },
+    /// Node stores a hash, which differs from the hash of its key.
+    WrongHash {
+        /// Hash, stored in the node.
+        stored: u64,
+        /// Hash of the key, computed by the tree's hasher.
+        expected: u64,
+    },
+    /// Two nodes store equal keys.
+    DuplicateKey {
+        /// Hash of the duplicated key.
+        hash: u64,
+    },
+    /// Node of an `AugmentedHashTree` stores a summary, which differs from
+    /// the summary of its subtree.
+    WrongSummary {
+        /// Hash of the node.
+        hash: u64,
+    },
+}
+
+impl core::fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InvariantError::UnorderedHash { previous, hash } => write!(
+                f,
+                "hash {} is placed after hash {} in hash order",
+                hash, previous
+            ),
+            InvariantError::WrongHash { stored, expected } => write!(
+                f,
+                "node stores hash {}, but its key hashes to {}",
+                stored, expected
+            ),
+            InvariantError::DuplicateKey { hash } => {
+                write!(f, "key with hash {} is stored twice", hash)
+            }
+            InvariantError::WrongSummary { hash } => {
+                write!(f, "node with hash {} stores a wrong summary", hash)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvariantError {}
+
+// ───── OccupiedError ────────────────────────────────────────────────────── //
+
+/// Returned by `HashTree::try_insert`, when the key is already present. The
+/// tree is left unchanged, and the rejected pair is given back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccupiedError<K, V> {
+    /// Key, which was not inserted.
+    pub key: K,
+    /// Value, which was not inserted.
+    pub value: V,
+}
+
+impl<K, V> core::fmt::Display for OccupiedError<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "key is already present")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: core::fmt::Debug, V: core::fmt::Debug> std::error::Error
+    for OccupiedError<K, V>
+{
+}
+
+// ───── TryReserveError ──────────────────────────────────────────────────── //
+
+/// Returned by fallible operations like `HashTree::try_insert_alloc`, when
+/// the allocator can't give memory for a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryReserveError {
+    layout: Layout,
+}
+
+impl TryReserveError {
+    pub(crate) fn new(layout: Layout) -> Self {
+        TryReserveError { layout }
+    }
+
+    /// Layout of the allocation, which failed.
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+}
+
+impl core::fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "memory allocation of {} bytes failed",
+            self.layout.size()
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryReserveError {}
+
+// ───── InvalidTokenError ────────────────────────────────────────────────── //
+
+/// Returned, when bytes or a string can't be parsed into a
+/// `ContinuationToken`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTokenError;
+
+impl core::fmt::Display for InvalidTokenError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid continuation token")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidTokenError {}
+
+// ───── UnorderedKeyError ────────────────────────────────────────────────── //
+
+/// Returned by `CursorMut::insert_before` and `CursorMut::insert_after`,
+/// when the key is already present, or its hash doesn't fit between the
+/// neighbours. The tree is left unchanged, and the rejected pair is given
+/// back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnorderedKeyError<K, V> {
+    /// Key, which was not inserted.
+    pub key: K,
+    /// Value, which was not inserted.
+    pub value: V,
+}
+
+impl<K, V> core::fmt::Display for UnorderedKeyError<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "key is present or out of hash order at the cursor")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: core::fmt::Debug, V: core::fmt::Debug> std::error::Error
+    for UnorderedKeyError<K, V>
+{
+}
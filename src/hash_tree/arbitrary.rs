@@ -0,0 +1,55 @@
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::HashTree;
+
+// ───── Arbitrary ────────────────────────────────────────────────────────── //
+
+/// Tree is built by inserting arbitrary pairs into a `HashTree` with an
+/// arbitrary seed, so the same input always produces the same tree shape.
+impl<'a, K, V> Arbitrary<'a> for HashTree<K, V>
+where
+    K: Arbitrary<'a> + core::hash::Hash + Eq,
+    V: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut tree = HashTree::new_with_seed(u.arbitrary()?);
+        for pair in u.arbitrary_iter::<(K, V)>()? {
+            let (key, value) = pair?;
+            tree.insert(key, value);
+        }
+        Ok(tree)
+    }
+
+    fn arbitrary_take_rest(mut u: Unstructured<'a>) -> Result<Self> {
+        let mut tree = HashTree::new_with_seed(u.arbitrary()?);
+        for pair in u.arbitrary_take_rest_iter::<(K, V)>()? {
+            let (key, value) = pair?;
+            tree.insert(key, value);
+        }
+        Ok(tree)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        // Seed is always present, pairs are not limited
+        (<u64 as Arbitrary>::size_hint(depth).0, None)
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arbitrary_tree() {
+        // Odd bytes are read as `true`, so pairs keep coming
+        let bytes: Vec<u8> =
+            (0..=255).cycle().take(4096).map(|b| b | 1).collect();
+        let tree: HashTree<u16, u8> =
+            HashTree::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap();
+
+        assert_eq!(tree.validate(), Ok(()));
+        assert!(tree.stats().len > 0);
+    }
+}
@@ -0,0 +1,793 @@
+//! `AugmentedHashTree` stores in every node a summary of its subtree, so an
+//! aggregate over any range of hashes is combined from O(height) summaries
+//! instead of a scan. Summaries are defined by a `Monoid` and recomputed on
+//! the path of every `insert` and `remove`, and after rotations of
+//! `rebalance`.
+
+use core::ops::{Bound, RangeBounds};
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::rebalance::{tree_to_vine, vine_to_tree};
+use super::{Equivalent, Side, TreeIter, TreeNode, TreePointer};
+use crate::error::InvariantError;
+
+// ───── Monoid ───────────────────────────────────────────────────────────── //
+
+/// Summary of values, which can be combined: `combine` must be associative,
+/// and `empty` must be its identity. Values are combined in hash order, so
+/// `combine` doesn't have to be commutative.
+pub trait Monoid<V> {
+    /// Summary of a subtree. Summaries are compared by
+    /// `AugmentedHashTree::validate`.
+    type Summary: Clone + PartialEq;
+
+    /// Summary of no values.
+    fn empty() -> Self::Summary;
+
+    /// Summary of one value.
+    fn summarize(value: &V) -> Self::Summary;
+
+    /// Summary of values of `left`, followed by values of `right`.
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+/// Number of values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Count;
+
+impl<V> Monoid<V> for Count {
+    type Summary = usize;
+
+    fn empty() -> usize {
+        0
+    }
+
+    fn summarize(_: &V) -> usize {
+        1
+    }
+
+    fn combine(left: &usize, right: &usize) -> usize {
+        left + right
+    }
+}
+
+/// Sum of values, `V::default()` is zero.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sum;
+
+impl<V> Monoid<V> for Sum
+where
+    V: Copy + Default + PartialEq + core::ops::Add<Output = V>,
+{
+    type Summary = V;
+
+    fn empty() -> V {
+        V::default()
+    }
+
+    fn summarize(value: &V) -> V {
+        *value
+    }
+
+    fn combine(left: &V, right: &V) -> V {
+        *left + *right
+    }
+}
+
+/// Minimal value, None if there are no values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Min;
+
+impl<V: Ord + Clone> Monoid<V> for Min {
+    type Summary = Option<V>;
+
+    fn empty() -> Option<V> {
+        None
+    }
+
+    fn summarize(value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(left: &Option<V>, right: &Option<V>) -> Option<V> {
+        match (left, right) {
+            (Some(left), Some(right)) => Some(left.min(right).clone()),
+            (left, right) => left.as_ref().or(right.as_ref()).cloned(),
+        }
+    }
+}
+
+/// Maximal value, None if there are no values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Max;
+
+impl<V: Ord + Clone> Monoid<V> for Max {
+    type Summary = Option<V>;
+
+    fn empty() -> Option<V> {
+        None
+    }
+
+    fn summarize(value: &V) -> Option<V> {
+        Some(value.clone())
+    }
+
+    fn combine(left: &Option<V>, right: &Option<V>) -> Option<V> {
+        match (left, right) {
+            (Some(left), Some(right)) => Some(left.max(right).clone()),
+            (left, right) => left.as_ref().or(right.as_ref()).cloned(),
+        }
+    }
+}
+
+// ───── AugmentedHashTree ────────────────────────────────────────────────── //
+
+/// Value of a node together with the summary of the node's subtree.
+struct Augmented<V, T> {
+    value: V,
+    summary: T,
+}
+
+type AugmentedNode<K, V, M, A> =
+    TreeNode<K, Augmented<V, <M as Monoid<V>>::Summary>, A>;
+
+type AugmentedPointer<K, V, M, A> =
+    TreePointer<K, Augmented<V, <M as Monoid<V>>::Summary>, A>;
+
+/// `HashTree`, which nodes keep summaries of their subtrees by monoid `M`,
+/// for aggregates over ranges of hashes.
+///
+/// ```
+/// use ghashy_collections::hash_tree::*;
+///
+/// let mut tree: AugmentedHashTree<u32, u64, Sum> =
+///     AugmentedHashTree::new_with_seed(1);
+/// for key in 0..100 {
+///     tree.insert(key, 1);
+/// }
+/// assert_eq!(tree.summary(), 100);
+/// assert_eq!(tree.aggregate_range_hash(..), 100);
+/// assert_eq!(tree.aggregate_range_hash(0..0), 0);
+/// ```
+pub struct AugmentedHashTree<
+    K,
+    V,
+    M,
+    S = ahash::RandomState,
+    A: Allocator = Global,
+> where
+    M: Monoid<V>,
+{
+    root: AugmentedPointer<K, V, M, A>,
+    state: S,
+    alloc: A,
+}
+
+impl<K, V, M> AugmentedHashTree<K, V, M>
+where
+    K: core::hash::Hash + Eq,
+    M: Monoid<V>,
+{
+    /// Create new empty `AugmentedHashTree`.
+    pub fn new() -> Self {
+        Self::with_hasher(ahash::RandomState::new())
+    }
+
+    /// Create new empty `AugmentedHashTree` with seeded hasher, like
+    /// `HashTree::new_with_seed`.
+    pub fn new_with_seed(seed: u64) -> Self {
+        Self::with_hasher(ahash::RandomState::with_seeds(
+            seed, seed, seed, seed,
+        ))
+    }
+}
+
+impl<K, V, M, S> AugmentedHashTree<K, V, M, S>
+where
+    K: core::hash::Hash + Eq,
+    M: Monoid<V>,
+    S: core::hash::BuildHasher,
+{
+    /// Create new empty `AugmentedHashTree`, which will use `state` to hash
+    /// keys.
+    pub fn with_hasher(state: S) -> Self {
+        Self::with_hasher_in(state, Global)
+    }
+}
+
+impl<K, V, M, A> AugmentedHashTree<K, V, M, ahash::RandomState, A>
+where
+    K: core::hash::Hash + Eq,
+    M: Monoid<V>,
+    A: Allocator + Clone,
+{
+    /// Create new empty `AugmentedHashTree`, which will allocate nodes in
+    /// `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_hasher_in(ahash::RandomState::new(), alloc)
+    }
+}
+
+impl<K, V, M, S, A> AugmentedHashTree<K, V, M, S, A>
+where
+    K: core::hash::Hash + Eq,
+    M: Monoid<V>,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Create new empty `AugmentedHashTree`, which will use `state` to hash
+    /// keys and allocate nodes in `alloc`.
+    pub fn with_hasher_in(state: S, alloc: A) -> Self {
+        AugmentedHashTree {
+            root: TreePointer::Empty,
+            state,
+            alloc,
+        }
+    }
+
+    /// Get a reference to the `BuildHasher` of this `AugmentedHashTree`.
+    pub fn hasher(&self) -> &S {
+        &self.state
+    }
+
+    /// Get a reference to the allocator of this `AugmentedHashTree`.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Insert an element. If a value is already present, the old value is
+    /// returned, otherwise None is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.state.hash_one(&key);
+        let mut path =
+            Path::<K, V, M, A>::new(&mut self.root, self.alloc.clone());
+        path.find(hash, &mut |k| *k == key);
+
+        let old_value = match path.place {
+            TreePointer::Empty => {
+                let summary = M::summarize(&value);
+                path.place = TreePointer::new(
+                    TreePointer::Empty,
+                    key,
+                    hash,
+                    Augmented { value, summary },
+                    TreePointer::Empty,
+                    self.alloc.clone(),
+                );
+                None
+            }
+            TreePointer::NonEmpty(ref mut node) => {
+                let old = core::mem::replace(&mut node.value.value, value);
+                update::<K, V, M, A>(node);
+                Some(old)
+            }
+        };
+        path.update();
+        self.check_invariants();
+        old_value
+    }
+
+    /// Get value by key, or None if not present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        match self.root.find(hash, &mut |k| key.equivalent(k)) {
+            TreePointer::Empty => None,
+            TreePointer::NonEmpty(node) => Some(&node.value.value),
+        }
+    }
+
+    /// Check if there is a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Remove pair, returns value, or None if not present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        let mut path =
+            Path::<K, V, M, A>::new(&mut self.root, self.alloc.clone());
+        path.find(hash, &mut |k| key.equivalent(k));
+        let removed = path.remove();
+        if removed.is_some() {
+            path.update();
+        } else {
+            // Nothing is changed, so summaries are up to date
+            drop(path);
+        }
+        self.check_invariants();
+        removed
+    }
+
+    /// Get iterator over pairs in hash order.
+    pub fn iter(&self) -> AugmentedIter<'_, K, V, M, A> {
+        AugmentedIter {
+            nodes: self.root.iter_in(self.alloc.clone()),
+        }
+    }
+
+    /// Get summary of all values in O(1).
+    pub fn summary(&self) -> M::Summary {
+        summary_of::<K, V, M, A>(&self.root)
+    }
+
+    /// Get summary of values, which key hashes are in `range`. Takes
+    /// O(height): subtrees, which are entirely in `range`, give their
+    /// summaries, and only two paths are descended.
+    pub fn aggregate_range_hash<R>(&self, range: R) -> M::Summary
+    where
+        R: RangeBounds<u64>,
+    {
+        aggregate::<K, V, M, A, R>(&self.root, &range, 0, u64::MAX)
+    }
+
+    /// Rebuild the tree into a complete tree of minimal height, like
+    /// `HashTree::rebalance`, then recompute all summaries in O(n).
+    pub fn rebalance(&mut self) {
+        let len = tree_to_vine(&mut self.root);
+        vine_to_tree(&mut self.root, len);
+        update_all::<K, V, M, A>(&mut self.root);
+        self.check_invariants();
+    }
+
+    /// Check that the tree is well formed, like `HashTree::validate`, and
+    /// that every node stores the summary of its subtree.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.root.validate(&self.state, self.alloc.clone())?;
+
+        let mut unvisited = AllocVec::new_in(self.alloc.clone());
+        unvisited.push(&self.root);
+        while let Some(tree_ptr) = unvisited.pop() {
+            if let TreePointer::NonEmpty(ref node) = *tree_ptr {
+                if node.value.summary != summarize::<K, V, M, A>(node) {
+                    return Err(InvariantError::WrongSummary {
+                        hash: node.hash,
+                    });
+                }
+                unvisited.push(&node.left);
+                unvisited.push(&node.right);
+            }
+        }
+        Ok(())
+    }
+
+    /// Panic if the tree is broken. Does nothing unless `debug-invariants`
+    /// feature is enabled in a debug build.
+    #[inline]
+    fn check_invariants(&self) {
+        #[cfg(all(debug_assertions, feature = "debug-invariants"))]
+        if let Err(error) = self.validate() {
+            panic!("AugmentedHashTree invariant is violated: {}", error);
+        }
+    }
+}
+
+impl<K, V, M> Default for AugmentedHashTree<K, V, M>
+where
+    K: core::hash::Hash + Eq,
+    M: Monoid<V>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, M, S, A> core::fmt::Debug for AugmentedHashTree<K, V, M, S, A>
+where
+    K: core::fmt::Debug + core::hash::Hash + Eq,
+    V: core::fmt::Debug,
+    M: Monoid<V>,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// ───── Summaries ────────────────────────────────────────────────────────── //
+
+fn summary_of<K, V, M: Monoid<V>, A: Allocator>(
+    tree_ptr: &AugmentedPointer<K, V, M, A>,
+) -> M::Summary {
+    match tree_ptr {
+        TreePointer::Empty => M::empty(),
+        TreePointer::NonEmpty(node) => node.value.summary.clone(),
+    }
+}
+
+/// Summary of `node`, computed from its value and its children.
+fn summarize<K, V, M: Monoid<V>, A: Allocator>(
+    node: &AugmentedNode<K, V, M, A>,
+) -> M::Summary {
+    let left = summary_of::<K, V, M, A>(&node.left);
+    let right = summary_of::<K, V, M, A>(&node.right);
+    let own = M::summarize(&node.value.value);
+    M::combine(&M::combine(&left, &own), &right)
+}
+
+/// Recompute summary of `node` from its value and its children.
+fn update<K, V, M: Monoid<V>, A: Allocator>(
+    node: &mut AugmentedNode<K, V, M, A>,
+) {
+    node.value.summary = summarize::<K, V, M, A>(node);
+}
+
+/// Recompute summaries of the whole subtree, children first.
+fn update_all<K, V, M: Monoid<V>, A: Allocator>(
+    tree_ptr: &mut AugmentedPointer<K, V, M, A>,
+) {
+    if let TreePointer::NonEmpty(node) = tree_ptr {
+        update_all::<K, V, M, A>(&mut node.left);
+        update_all::<K, V, M, A>(&mut node.right);
+        update::<K, V, M, A>(node);
+    }
+}
+
+fn aggregate<K, V, M, A, R>(
+    tree_ptr: &AugmentedPointer<K, V, M, A>,
+    range: &R,
+    lowest: u64,
+    highest: u64,
+) -> M::Summary
+where
+    M: Monoid<V>,
+    A: Allocator,
+    R: RangeBounds<u64>,
+{
+    let node = match tree_ptr {
+        TreePointer::Empty => return M::empty(),
+        TreePointer::NonEmpty(node) => node,
+    };
+    // All hashes of the subtree are in `lowest..=highest`
+    if range.contains(&lowest) && range.contains(&highest) {
+        return node.value.summary.clone();
+    }
+
+    let after_start = match range.start_bound() {
+        Bound::Included(&start) => node.hash >= start,
+        Bound::Excluded(&start) => node.hash > start,
+        Bound::Unbounded => true,
+    };
+    let before_end = match range.end_bound() {
+        Bound::Included(&end) => node.hash <= end,
+        Bound::Excluded(&end) => node.hash < end,
+        Bound::Unbounded => true,
+    };
+
+    // Colliding hashes may be on both sides, so bounds of children include
+    // the hash of the node
+    let left = if after_start {
+        aggregate::<K, V, M, A, R>(&node.left, range, lowest, node.hash)
+    } else {
+        M::empty()
+    };
+    let own = if after_start && before_end {
+        M::summarize(&node.value.value)
+    } else {
+        M::empty()
+    };
+    let right = if before_end {
+        aggregate::<K, V, M, A, R>(&node.right, range, node.hash, highest)
+    } else {
+        M::empty()
+    };
+    M::combine(&M::combine(&left, &own), &right)
+}
+
+// ───── Path ───────────────────────────────────────────────────────────── //
+
+/// Nodes, detached from their parents, with sides of their detached children.
+type Detached<K, V, M, A> =
+    AllocVec<(Box<AugmentedNode<K, V, M, A>, A>, Side), A>;
+
+/// Nodes from the root to a place in the tree, detached from their parents.
+/// The place can be changed, and then summaries on the path are updated
+/// bottom-up in O(height), without recursion. Nodes are attached back on
+/// drop, even if `M` or `Eq` of keys panics, then only summaries are stale.
+struct Path<'a, K, V, M: Monoid<V>, A: Allocator + Clone> {
+    root: &'a mut AugmentedPointer<K, V, M, A>,
+    /// Detached nodes from the root, each with the side of the next one.
+    nodes: Detached<K, V, M, A>,
+    /// Subtree at the end of the path.
+    place: AugmentedPointer<K, V, M, A>,
+}
+
+impl<'a, K, V, M: Monoid<V>, A: Allocator + Clone> Path<'a, K, V, M, A> {
+    fn new(root: &'a mut AugmentedPointer<K, V, M, A>, alloc: A) -> Self {
+        let place = root.take();
+        Path {
+            root,
+            nodes: AllocVec::new_in(alloc),
+            place,
+        }
+    }
+
+    /// Descend to the node with `hash`, which key satisfies `is_match`, or
+    /// to the empty place for it. Turns are found by `find_mut`, which
+    /// looks at the tree, before it is detached.
+    fn find<F>(&mut self, hash: u64, is_match: &mut F)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut sides = AllocVec::new_in(self.nodes.allocator().clone());
+        self.place
+            .find_mut_path(hash, is_match, &mut |side| sides.push(side));
+        for side in sides {
+            self.descend(side);
+        }
+    }
+
+    /// Detach the child of the place on `side`, and make it the place.
+    fn descend(&mut self, side: Side) {
+        let child = self.place.as_mut().child_mut(side).take();
+        let node = self.place.replace(child).unwrap();
+        self.nodes.push((node, side));
+    }
+
+    /// Remove the node at the place, returns its value, or None if the
+    /// place is empty.
+    fn remove(&mut self) -> Option<V> {
+        let node = match self.place {
+            TreePointer::Empty => return None,
+            TreePointer::NonEmpty(ref mut node) => node,
+        };
+        if node.left.is_non_empty() && node.right.is_non_empty() {
+            // Minimal node of the right subtree takes place of the removed
+            // one, which stays on the path
+            let depth = self.nodes.len();
+            self.descend(Side::Right);
+            while self.place.as_ref().left.is_non_empty() {
+                self.descend(Side::Left);
+            }
+            let right = self.place.as_mut().right.take();
+            let mut min = self.place.replace(right).unwrap();
+            let node = &mut self.nodes[depth].0;
+            core::mem::swap(&mut node.key, &mut min.key);
+            core::mem::swap(&mut node.hash, &mut min.hash);
+            core::mem::swap(&mut node.value.value, &mut min.value.value);
+            return Some(Box::into_inner(min).value.value);
+        }
+
+        let child = if node.left.is_non_empty() {
+            node.left.take()
+        } else {
+            node.right.take()
+        };
+        let node = Box::into_inner(self.place.replace(child).unwrap());
+        Some(node.value.value)
+    }
+
+    /// Attach nodes back, updating their summaries, the deepest first.
+    fn update(mut self) {
+        while let Some((mut node, side)) = self.nodes.pop() {
+            *node.child_mut(side) = self.place.take();
+            update::<K, V, M, A>(&mut node);
+            self.place = TreePointer::NonEmpty(node);
+        }
+    }
+}
+
+impl<'a, K, V, M, A> Drop for Path<'a, K, V, M, A>
+where
+    M: Monoid<V>,
+    A: Allocator + Clone,
+{
+    fn drop(&mut self) {
+        while let Some((mut node, side)) = self.nodes.pop() {
+            *node.child_mut(side) = self.place.take();
+            self.place = TreePointer::NonEmpty(node);
+        }
+        *self.root = self.place.take();
+    }
+}
+
+// ───── AugmentedIter ────────────────────────────────────────────────────── //
+
+/// Iterator over pairs of `AugmentedHashTree` in hash order.
+pub struct AugmentedIter<'a, K, V, M: Monoid<V>, A: Allocator = Global> {
+    nodes: TreeIter<'a, K, Augmented<V, M::Summary>, A>,
+}
+
+impl<'a, K, V, M, A> Iterator for AugmentedIter<'a, K, V, M, A>
+where
+    M: Monoid<V>,
+    A: Allocator,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.nodes.next()?;
+        Some((key, &value.value))
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::model_tests::CollidingState;
+
+    /// Sum of values with hashes in `range`, found by a scan.
+    fn scan<M, R>(tree: &AugmentedHashTree<u32, u64, M>, range: R) -> u64
+    where
+        M: Monoid<u64>,
+        R: RangeBounds<u64>,
+    {
+        tree.iter()
+            .filter(|(key, _)| range.contains(&tree.hasher().hash_one(key)))
+            .map(|(_, value)| value)
+            .sum()
+    }
+
+    #[test]
+    fn test_aggregate_range_hash() {
+        let mut tree: AugmentedHashTree<u32, u64, Sum> =
+            AugmentedHashTree::new_with_seed(1);
+        for key in 0..1000 {
+            tree.insert(key, u64::from(key));
+        }
+        tree.insert(10, 1000);
+        for key in (0..1000).step_by(3) {
+            tree.remove(&key);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+
+        let hashes: Vec<u64> = tree
+            .iter()
+            .map(|(key, _)| tree.hasher().hash_one(key))
+            .collect();
+        for (i, j) in [(0, 0), (0, 10), (5, 200), (100, 101), (300, 600)] {
+            let (start, end) = (hashes[i], hashes[j]);
+            assert_eq!(
+                tree.aggregate_range_hash(start..end),
+                scan(&tree, start..end)
+            );
+            assert_eq!(
+                tree.aggregate_range_hash(start..=end),
+                scan(&tree, start..=end)
+            );
+            assert_eq!(tree.aggregate_range_hash(..end), scan(&tree, ..end));
+            assert_eq!(
+                tree.aggregate_range_hash(start..),
+                scan(&tree, start..)
+            );
+        }
+        assert_eq!(tree.summary(), scan(&tree, ..));
+        assert_eq!(tree.get(&10), Some(&1000));
+        assert_eq!(tree.get(&9), None);
+    }
+
+    #[test]
+    fn test_builtin_monoids() {
+        let mut count: AugmentedHashTree<u32, u64, Count> =
+            AugmentedHashTree::new_with_seed(1);
+        let mut min: AugmentedHashTree<u32, u64, Min> =
+            AugmentedHashTree::new_with_seed(1);
+        let mut max: AugmentedHashTree<u32, u64, Max> =
+            AugmentedHashTree::new_with_seed(1);
+        assert_eq!(min.summary(), None);
+        for key in 0..100 {
+            count.insert(key, 0);
+            min.insert(key, u64::from(key) + 5);
+            max.insert(key, u64::from(key) + 5);
+        }
+        min.remove(&0);
+        max.remove(&99);
+        assert_eq!(min.validate(), Ok(()));
+        assert_eq!(max.validate(), Ok(()));
+
+        assert_eq!(count.summary(), 100);
+        assert_eq!(count.aggregate_range_hash(..u64::MAX / 2), {
+            let half = (0..100u32)
+                .filter(|key| count.hasher().hash_one(key) < u64::MAX / 2);
+            half.count()
+        });
+        assert_eq!(min.summary(), Some(6));
+        assert_eq!(max.summary(), Some(103));
+    }
+
+    #[test]
+    fn test_rebalance() {
+        let mut tree: AugmentedHashTree<u32, u64, Sum> =
+            AugmentedHashTree::new_with_seed(1);
+        for key in 0..1000 {
+            tree.insert(key, 1);
+        }
+        tree.rebalance();
+
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.summary(), 1000);
+        assert_eq!(tree.remove(&5), Some(1));
+        assert_eq!(tree.summary(), 999);
+    }
+
+    #[test]
+    fn test_validate_summaries() {
+        let mut tree: AugmentedHashTree<u32, u64, Sum> =
+            AugmentedHashTree::new_with_seed(1);
+        for key in 0..10 {
+            tree.insert(key, 1);
+        }
+        assert_eq!(tree.validate(), Ok(()));
+
+        let root = tree.root.as_mut();
+        root.value.summary += 1;
+        let hash = root.hash;
+        assert_eq!(tree.validate(), Err(InvariantError::WrongSummary { hash }));
+    }
+
+    /// Sum of values, which panics on `u64::MAX`.
+    struct PanickingSum;
+
+    impl Monoid<u64> for PanickingSum {
+        type Summary = u64;
+
+        fn empty() -> u64 {
+            0
+        }
+
+        fn summarize(value: &u64) -> u64 {
+            assert_ne!(*value, u64::MAX, "value is too large");
+            *value
+        }
+
+        fn combine(left: &u64, right: &u64) -> u64 {
+            left + right
+        }
+    }
+
+    #[test]
+    fn test_monoid_panic() {
+        let mut tree: AugmentedHashTree<u32, u64, PanickingSum> =
+            AugmentedHashTree::new_with_seed(1);
+        for key in 0..100 {
+            tree.insert(key, 1);
+        }
+
+        // Nodes, detached on the path to the new one, are attached back
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                tree.insert(1000, u64::MAX)
+            }));
+        assert!(result.is_err());
+        for key in 0..100 {
+            assert_eq!(tree.get(&key), Some(&1));
+        }
+        assert_eq!(tree.iter().count(), 100);
+    }
+
+    #[test]
+    fn test_augmented_with_collisions() {
+        let state = CollidingState::new(1, 0b11);
+        let mut tree: AugmentedHashTree<u16, u32, Sum, _> =
+            AugmentedHashTree::with_hasher(state);
+        for key in 0..100u16 {
+            assert_eq!(tree.insert(key, u32::from(key)), None);
+        }
+        assert_eq!(tree.insert(7, 0), Some(7));
+        for key in (0..100).step_by(3) {
+            assert_eq!(tree.remove(&key), Some(u32::from(key)));
+        }
+
+        let expected: u32 =
+            (0..100).filter(|key| key % 3 != 0 && *key != 7).sum();
+        assert_eq!(tree.summary(), expected);
+        assert_eq!(tree.aggregate_range_hash(..), expected);
+        for key in 0..100 {
+            assert_eq!(tree.contains_key(&key), key % 3 != 0);
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use core::iter::Peekable;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, TreeNode, TreePointer};
+
+/// `Extend` and `FromIterator` switch from `insert` to `bulk_load` on an
+/// empty tree, when `size_hint` promises at least this many pairs.
+const BULK_LOAD_THRESHOLD: usize = 64;
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    /// Create a `HashTree` of minimal height from pairs of `iter`. If a key
+    /// comes several times, the last value wins, like with `insert`.
+    pub fn from_iter_balanced<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut tree = HashTree::with_hasher_in(S::default(), A::default());
+        tree.bulk_load(iter);
+        tree
+    }
+}
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Insert all pairs of `iter` and rebuild the tree with minimal height.
+    /// If a key comes several times, the last value wins, like with `insert`.
+    ///
+    /// Takes `O(n + m log m)` for `n` pairs in the tree and `m` pairs in
+    /// `iter`: only new pairs are sorted, the rest is linear.
+    pub fn bulk_load<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.bulk_load_with(iter, |_, old, new| *old = new)
+    }
+
+    /// Same as `bulk_load`, but a duplicated key is resolved by `merge`,
+    /// which gets the key, the value already in the tree (or met earlier in
+    /// `iter`) and the new value.
+    pub fn bulk_load_with<I, F>(&mut self, iter: I, merge: F)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, &mut V, V),
+    {
+        let iter = iter.into_iter();
+        let mut entries =
+            AllocVec::with_capacity_in(iter.size_hint().0, self.alloc.clone());
+        for (index, (key, value)) in iter.enumerate() {
+            entries.push((self.state.hash_one(&key), index, key, value));
+        }
+        // Unstable sort doesn't allocate, and `index` keeps duplicated keys
+        // in the order they came
+        entries.sort_unstable_by_key(|&(hash, index, _, _)| (hash, index));
+
+        let sorted = entries
+            .into_iter()
+            .map(|(hash, _, key, value)| (hash, key, value));
+        self.load_sorted(sorted, merge);
+    }
+
+    /// Merge `sorted` triples `(hash, key, value)`, which come in hash order,
+    /// into the tree and rebuild it with minimal height. Pairs of the tree
+    /// are older than pairs of `sorted` for `merge`.
+    ///
+    /// If `merge` or `Eq` of keys panics, the tree is rebuilt from pairs,
+    /// which were in it or were merged before the panic.
+    pub(super) fn load_sorted<I, F>(&mut self, sorted: I, mut merge: F)
+    where
+        I: Iterator<Item = (u64, K, V)>,
+        F: FnMut(&K, &mut V, V),
+    {
+        let alloc = self.alloc.clone();
+        let mut guard = LoadGuard {
+            old: IntoSorted::new(self.root.take(), alloc.clone()).peekable(),
+            unique: AllocVec::new_in(alloc.clone()),
+            root: &mut self.root,
+            alloc,
+        };
+        let (old, unique) = (&mut guard.old, &mut guard.unique);
+        let mut new = sorted.peekable();
+
+        loop {
+            let take_old = match (old.peek(), new.peek()) {
+                (Some(o), Some(n)) => o.0 <= n.0,
+                (o, _) => o.is_some(),
+            };
+            let next = if take_old { old.next() } else { new.next() };
+            let (hash, key, value) = match next {
+                Some(triple) => triple,
+                None => break,
+            };
+
+            // Equal keys have equal hashes, so they are close to each other
+            let duplicate = unique
+                .iter_mut()
+                .rev()
+                .take_while(|entry| entry.0 == hash)
+                .find(|entry| entry.1 == key);
+            match duplicate {
+                Some(entry) => merge(&entry.1, &mut entry.2, value),
+                None => unique.push((hash, key, value)),
+            }
+        }
+
+        // The guard builds the tree from `unique`
+        drop(guard);
+        self.check_invariants();
+    }
+}
+
+impl<K, V, S, A> FromIterator<(K, V)> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = HashTree::with_hasher_in(S::default(), A::default());
+        tree.extend(iter);
+        tree
+    }
+}
+
+/// Large inputs are added to an empty tree with `bulk_load`, which builds a
+/// tree of minimal height. Otherwise pairs are added with `insert`, which
+/// doesn't touch the rest of the tree.
+impl<K, V, S, A> Extend<(K, V)> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        if !self.root.is_non_empty()
+            && iter.size_hint().0 >= BULK_LOAD_THRESHOLD
+        {
+            self.bulk_load(iter);
+        } else {
+            for (key, value) in iter {
+                self.insert(key, value);
+            }
+        }
+    }
+}
+
+impl<'a, K, V, S, A> Extend<(&'a K, &'a V)> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq + Copy,
+    V: Copy,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+// ───── Helpers ──────────────────────────────────────────────────────────── //
+
+/// Build a tree of minimal height from the first `len` triples of `sorted`,
+/// which yields `(hash, key, value)` in hash order.
+fn build_balanced<K, V, A, I>(
+    sorted: &mut I,
+    len: usize,
+    alloc: &A,
+) -> TreePointer<K, V, A>
+where
+    A: Allocator + Clone,
+    I: Iterator<Item = (u64, K, V)>,
+{
+    if len == 0 {
+        return TreePointer::Empty;
+    }
+    let left = build_balanced(sorted, len / 2, alloc);
+    let (hash, key, value) = sorted.next().expect("too few pairs");
+    let right = build_balanced(sorted, len - len / 2 - 1, alloc);
+    TreePointer::new(left, key, hash, value, right, alloc.clone())
+}
+
+/// Pairs of `HashTree::load_sorted`, which puts them back into the tree on
+/// drop, after the merge or on unwind.
+struct LoadGuard<'t, K, V, A: Allocator + Clone> {
+    root: &'t mut TreePointer<K, V, A>,
+    /// Pairs of the tree, which are not merged yet.
+    old: Peekable<IntoSorted<K, V, A>>,
+    /// Merged pairs in hash order.
+    unique: AllocVec<(u64, K, V), A>,
+    alloc: A,
+}
+
+impl<K, V, A: Allocator + Clone> Drop for LoadGuard<'_, K, V, A> {
+    fn drop(&mut self) {
+        // Pairs of the tree are taken first on equal hashes, so the rest of
+        // them comes after all merged pairs
+        let mut unique = core::mem::replace(
+            &mut self.unique,
+            AllocVec::new_in(self.alloc.clone()),
+        );
+        unique.extend(&mut self.old);
+        let len = unique.len();
+        *self.root = build_balanced(&mut unique.into_iter(), len, &self.alloc);
+    }
+}
+
+/// Consuming in-order iteration, which yields `(hash, key, value)`.
+pub(super) struct IntoSorted<K, V, A: Allocator> {
+    /// Same as in `TreeIter`, but nodes are owned and their left subtrees
+    /// are already taken.
+    unvisited: AllocVec<Box<TreeNode<K, V, A>, A>, A>,
+}
+
+impl<K, V, A: Allocator> IntoSorted<K, V, A> {
+    pub(super) fn new(root: TreePointer<K, V, A>, alloc: A) -> Self {
+        let mut iter = IntoSorted {
+            unvisited: AllocVec::new_in(alloc),
+        };
+        iter.push_left_edge(root);
+        iter
+    }
+
+    fn push_left_edge(&mut self, mut tree_ptr: TreePointer<K, V, A>) {
+        while let TreePointer::NonEmpty(mut node) = tree_ptr {
+            tree_ptr = node.left.take();
+            self.unvisited.push(node);
+        }
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for IntoSorted<K, V, A> {
+    type Item = (u64, K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = Box::into_inner(self.unvisited.pop()?);
+        self.push_left_edge(node.right);
+        Some((node.hash, node.key, node.value))
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::{TraversalOrder, TreeStats};
+
+    /// `ahash::RandomState` has no `Default` without `std` feature.
+    type FixedState = core::hash::BuildHasherDefault<ahash::AHasher>;
+
+    #[test]
+    fn test_from_iter_balanced() {
+        for len in [0, 1, 2, 3, 100, 1000] {
+            let tree: HashTree<u32, u32, FixedState> =
+                HashTree::from_iter_balanced((0..len).map(|i| (i, i * 2)));
+            let stats = tree.stats();
+
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(stats.len, len as usize);
+            assert_eq!(stats.height, TreeStats::optimal_height(len as usize));
+            for key in 0..len {
+                assert_eq!(tree.get(&key), Some(&(key * 2)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_last_value_wins() {
+        let pairs = [(1, "a"), (2, "b"), (1, "c"), (3, "d"), (1, "e")];
+        let tree: HashTree<u32, &str, FixedState> =
+            HashTree::from_iter_balanced(pairs);
+
+        assert_eq!(tree.stats().len, 3);
+        assert_eq!(tree[&1], "e");
+        assert_eq!(tree[&2], "b");
+        assert_eq!(tree[&3], "d");
+    }
+
+    #[test]
+    fn test_bulk_load_with() {
+        let mut tree = HashTree::new_with_seed(1);
+        tree.insert(1, 10);
+        tree.insert(2, 20);
+
+        // Sum values of duplicated keys
+        tree.bulk_load_with([(1, 1), (3, 3), (1, 100)], |_, old, new| {
+            *old += new
+        });
+
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.stats().len, 3);
+        assert_eq!(tree.get(&1), Some(&111));
+        assert_eq!(tree.get(&2), Some(&20));
+        assert_eq!(tree.get(&3), Some(&3));
+    }
+
+    #[test]
+    fn test_bulk_load_rebalances() {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..500u32 {
+            tree.insert(key, key);
+        }
+        tree.bulk_load((500..1000).map(|key| (key, key)));
+
+        let stats = tree.stats();
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(stats.len, 1000);
+        assert_eq!(stats.height, TreeStats::optimal_height(1000));
+    }
+
+    #[test]
+    fn test_bulk_load_panic() {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..100u32 {
+            tree.insert(key, key);
+        }
+
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                tree.bulk_load_with(
+                    (50..150).map(|k| (k, 0)),
+                    |_, _, _| panic!(),
+                )
+            }));
+        assert!(result.is_err());
+        assert_eq!(tree.validate(), Ok(()));
+        for key in 0..100 {
+            assert_eq!(tree.get(&key), Some(&key));
+        }
+    }
+
+    #[test]
+    fn test_extend_keeps_shape() {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..1000u32 {
+            tree.insert(key, key);
+        }
+        let root = *tree.traverse(TraversalOrder::PreOrder).next().unwrap().key;
+
+        // Large input goes through `insert`, so the root stays in place
+        tree.extend((1000..1000 + BULK_LOAD_THRESHOLD as u32).map(|k| (k, k)));
+        let new_root = tree.traverse(TraversalOrder::PreOrder).next().unwrap();
+        assert_eq!(*new_root.key, root);
+        assert_eq!(tree.stats().len, 1000 + BULK_LOAD_THRESHOLD);
+    }
+
+    #[test]
+    fn test_collect_and_extend() {
+        // Small input is inserted one by one
+        let mut tree: HashTree<u32, u32, FixedState> =
+            (0..10).map(|i| (i, i)).collect();
+        assert_eq!(tree.stats().len, 10);
+
+        tree.extend((0..BULK_LOAD_THRESHOLD as u32 * 2).map(|i| (i, i + 1)));
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.stats().len, BULK_LOAD_THRESHOLD * 2);
+        assert_eq!(tree.get(&0), Some(&1));
+
+        let source: HashTree<u32, u32, FixedState> =
+            (1000..1010).map(|i| (i, i)).collect();
+        tree.extend(&source);
+        assert_eq!(tree.stats().len, BULK_LOAD_THRESHOLD * 2 + 10);
+        assert_eq!(tree.get(&1005), Some(&1005));
+    }
+}
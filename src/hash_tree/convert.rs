@@ -0,0 +1,266 @@
+//! Conversions between `HashTree` and other maps. With `From` hash maps may
+//! use any `BuildHasher`, so keys are hashed again with the hasher of the
+//! target, which is created with `Default`. `HashTree::into_hashbrown` and
+//! `HashTree::from_hashbrown` keep the hasher instead, so a `HashTree` can
+//! give its stored hashes to `hashbrown::HashMap`. A new `HashTree` is built
+//! with `bulk_load`, so it comes out balanced.
+
+use alloc::collections::BTreeMap;
+
+use allocator_api2::alloc::Allocator;
+
+use super::bulk::IntoSorted;
+use super::HashTree;
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Convert into `hashbrown::HashMap` with the same hasher. Stored hashes
+    /// are reused, so keys are not hashed again.
+    pub fn into_hashbrown(self) -> hashbrown::HashMap<K, V, S> {
+        use hashbrown::hash_map::RawEntryMut;
+
+        let len = (&self).into_iter().count();
+        let (state, pairs) = into_parts(self);
+        let mut map = hashbrown::HashMap::with_capacity_and_hasher(len, state);
+        for (hash, key, value) in pairs {
+            // Keys of a tree are unique, so the entry is always vacant
+            if let RawEntryMut::Vacant(entry) =
+                map.raw_entry_mut().from_key_hashed_nocheck(hash, &key)
+            {
+                entry.insert_hashed_nocheck(hash, key, value);
+            }
+        }
+        map
+    }
+}
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Clone,
+    A: Allocator + Clone + Default,
+{
+    /// Create a `HashTree` of minimal height with pairs and the hasher of
+    /// `map`. `hashbrown::HashMap` doesn't keep whole hashes, so every key is
+    /// hashed once, but unlike `From` no hasher is created with `Default`.
+    pub fn from_hashbrown(map: hashbrown::HashMap<K, V, S>) -> Self {
+        let mut tree =
+            HashTree::with_hasher_in(map.hasher().clone(), A::default());
+        tree.bulk_load(map);
+        tree
+    }
+}
+
+// ───── Into HashTree ────────────────────────────────────────────────────── //
+
+impl<K, V, S, A, const N: usize> From<[(K, V); N]> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn from(pairs: [(K, V); N]) -> Self {
+        HashTree::from_iter_balanced(pairs)
+    }
+}
+
+impl<K, V, S, A> From<BTreeMap<K, V>> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn from(map: BTreeMap<K, V>) -> Self {
+        HashTree::from_iter_balanced(map)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, S2, A> From<std::collections::HashMap<K, V, S2>>
+    for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn from(map: std::collections::HashMap<K, V, S2>) -> Self {
+        HashTree::from_iter_balanced(map)
+    }
+}
+
+impl<K, V, S, S2, A> From<hashbrown::HashMap<K, V, S2>> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher + Default,
+    A: Allocator + Clone + Default,
+{
+    fn from(map: hashbrown::HashMap<K, V, S2>) -> Self {
+        HashTree::from_iter_balanced(map)
+    }
+}
+
+// ───── From HashTree ────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> From<HashTree<K, V, S, A>> for BTreeMap<K, V>
+where
+    K: Ord,
+    A: Allocator + Clone,
+{
+    fn from(tree: HashTree<K, V, S, A>) -> Self {
+        let (_, pairs) = into_parts(tree);
+        pairs.map(|(_, key, value)| (key, value)).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, S2, A> From<HashTree<K, V, S, A>>
+    for std::collections::HashMap<K, V, S2>
+where
+    K: core::hash::Hash + Eq,
+    S2: core::hash::BuildHasher + Default,
+    A: Allocator + Clone,
+{
+    fn from(tree: HashTree<K, V, S, A>) -> Self {
+        let len = (&tree).into_iter().count();
+        let (_, pairs) = into_parts(tree);
+        let mut map = std::collections::HashMap::with_capacity_and_hasher(
+            len,
+            S2::default(),
+        );
+        map.extend(pairs.map(|(_, key, value)| (key, value)));
+        map
+    }
+}
+
+impl<K, V, S, S2, A> From<HashTree<K, V, S, A>> for hashbrown::HashMap<K, V, S2>
+where
+    K: core::hash::Hash + Eq,
+    S2: core::hash::BuildHasher + Default,
+    A: Allocator + Clone,
+{
+    fn from(tree: HashTree<K, V, S, A>) -> Self {
+        let len = (&tree).into_iter().count();
+        let (_, pairs) = into_parts(tree);
+        let mut map =
+            hashbrown::HashMap::with_capacity_and_hasher(len, S2::default());
+        map.extend(pairs.map(|(_, key, value)| (key, value)));
+        map
+    }
+}
+
+/// Split `tree` into its hasher and its pairs in hash order.
+fn into_parts<K, V, S, A>(
+    tree: HashTree<K, V, S, A>,
+) -> (S, IntoSorted<K, V, A>)
+where
+    A: Allocator + Clone,
+{
+    (tree.state, IntoSorted::new(tree.root, tree.alloc))
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::TreeStats;
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    type FixedState = core::hash::BuildHasherDefault<ahash::AHasher>;
+
+    /// Hasher, which counts hashed keys.
+    #[derive(Clone, Default)]
+    struct CountingState {
+        hashed: Rc<Cell<usize>>,
+    }
+
+    impl core::hash::BuildHasher for CountingState {
+        type Hasher = ahash::AHasher;
+
+        fn build_hasher(&self) -> ahash::AHasher {
+            self.hashed.set(self.hashed.get() + 1);
+            ahash::AHasher::default()
+        }
+    }
+
+    fn create_tree() -> HashTree<u32, u32, FixedState> {
+        (0..100).map(|key| (key, key * 2)).collect()
+    }
+
+    #[test]
+    fn test_from_array() {
+        let tree: HashTree<&str, u32, FixedState> =
+            HashTree::from([("a", 1), ("b", 2), ("c", 3), ("a", 4)]);
+
+        assert_eq!(tree.stats().height, TreeStats::optimal_height(3));
+        assert_eq!(tree["a"], 4);
+        assert_eq!(tree["c"], 3);
+    }
+
+    #[test]
+    fn test_btree_map() {
+        let map: BTreeMap<u32, u32> = create_tree().into();
+        assert_eq!(map, (0..100).map(|key| (key, key * 2)).collect());
+
+        let tree: HashTree<u32, u32, FixedState> = map.into();
+        assert_eq!(tree, create_tree());
+        assert_eq!(tree.stats().height, TreeStats::optimal_height(100));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_std_hash_map() {
+        let tree: HashTree<u32, u32> = (0..100).map(|k| (k, k * 2)).collect();
+        let map: std::collections::HashMap<u32, u32> = tree.into();
+        assert_eq!(map.len(), 100);
+        assert_eq!(map[&10], 20);
+
+        let tree: HashTree<u32, u32> = map.into();
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.stats().height, TreeStats::optimal_height(100));
+        for key in 0..100 {
+            assert_eq!(tree.get(&key), Some(&(key * 2)));
+        }
+    }
+
+    #[test]
+    fn test_hashbrown_map() {
+        let map: hashbrown::HashMap<u32, u32> = create_tree().into();
+        assert_eq!(map.len(), 100);
+        for key in 0..100 {
+            assert_eq!(map.get(&key), Some(&(key * 2)));
+        }
+
+        let tree: HashTree<u32, u32, FixedState> = map.into();
+        assert_eq!(tree, create_tree());
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_hashbrown_shared_hasher() {
+        let state = CountingState::default();
+        let mut tree = HashTree::with_hasher(state.clone());
+        tree.bulk_load((0..100u32).map(|key| (key, key * 2)));
+        let expected = tree.clone();
+
+        state.hashed.set(0);
+        let map = tree.into_hashbrown();
+        assert_eq!(state.hashed.get(), 0);
+        assert_eq!(map.len(), 100);
+        for key in 0..100 {
+            assert_eq!(map.get(&key), Some(&(key * 2)));
+        }
+
+        let tree: HashTree<u32, u32, CountingState> =
+            HashTree::from_hashbrown(map);
+        assert_eq!(tree, expected);
+        assert_eq!(tree.stats().height, TreeStats::optimal_height(100));
+    }
+}
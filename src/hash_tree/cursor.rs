@@ -0,0 +1,790 @@
+//! Cursors point to a pair of the tree, or to the "ghost" position between
+//! the last and the first pairs, and move in hash order both ways.
+//!
+//! `Cursor` keeps references to all ancestors of its node, so a move takes
+//! amortized O(1). `CursorMut` can't keep mutable references to ancestors
+//! without unsafe code, so it keeps sides of turns from the root to its
+//! node instead, and follows them on every access and move: it takes
+//! O(height), but without any comparisons of hashes or keys.
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, Side, TreeNode, TreePointer, UnorderedKeyError};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get cursor, pointing to the first pair in hash order, or to the ghost
+    /// position if the tree is empty.
+    pub fn cursor_front(&self) -> Cursor<'_, K, V, A> {
+        Cursor::front(&self.root, self.alloc.clone())
+    }
+
+    /// Get cursor, pointing to the last pair in hash order, or to the ghost
+    /// position if the tree is empty.
+    pub fn cursor_back(&self) -> Cursor<'_, K, V, A> {
+        let mut cursor = Cursor::ghost(&self.root, self.alloc.clone());
+        cursor.move_prev();
+        cursor
+    }
+
+    /// Get cursor, pointing to the first pair in hash order with hash not
+    /// less than `hash`, or to the ghost position if there is no such pair.
+    ///
+    /// ```
+    /// use core::hash::BuildHasher;
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new_with_seed(1);
+    /// for key in 0..10 {
+    ///     tree.insert(key, key);
+    /// }
+    /// let hash = tree.hasher().hash_one(5);
+    /// let mut cursor = tree.lower_bound_hash(hash);
+    /// assert_eq!(cursor.key_value(), Some((&5, &5)));
+    /// cursor.move_prev();
+    /// assert_eq!(cursor.peek_next(), Some((&5, &5)));
+    /// ```
+    pub fn lower_bound_hash(&self, hash: u64) -> Cursor<'_, K, V, A> {
+        Cursor::lower_bound(&self.root, hash, self.alloc.clone())
+    }
+
+    /// Same as `cursor_front`, but the cursor can change the tree.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, V, S, A> {
+        let sides = self.cursor_front().sides();
+        CursorMut::new(self, sides)
+    }
+
+    /// Same as `cursor_back`, but the cursor can change the tree.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, K, V, S, A> {
+        let sides = self.cursor_back().sides();
+        CursorMut::new(self, sides)
+    }
+
+    /// Same as `lower_bound_hash`, but the cursor can change the tree.
+    pub fn lower_bound_hash_mut(
+        &mut self,
+        hash: u64,
+    ) -> CursorMut<'_, K, V, S, A> {
+        let sides = self.lower_bound_hash(hash).sides();
+        CursorMut::new(self, sides)
+    }
+}
+
+// ───── Cursor ───────────────────────────────────────────────────────────── //
+
+/// Read-only cursor over a `HashTree`.
+pub struct Cursor<'a, K, V, A: Allocator = Global> {
+    root: &'a TreePointer<K, V, A>,
+    /// Nodes from the root to the current one. Empty at the ghost position.
+    path: AllocVec<&'a TreeNode<K, V, A>, A>,
+}
+
+impl<'a, K, V, A: Allocator> Cursor<'a, K, V, A> {
+    pub(super) fn ghost(root: &'a TreePointer<K, V, A>, alloc: A) -> Self {
+        Cursor {
+            root,
+            path: AllocVec::new_in(alloc),
+        }
+    }
+
+    pub(super) fn front(root: &'a TreePointer<K, V, A>, alloc: A) -> Self {
+        let mut cursor = Cursor::ghost(root, alloc);
+        cursor.move_next();
+        cursor
+    }
+
+    /// Cursor, pointing to the first node with hash not less than `hash`.
+    pub(super) fn lower_bound(
+        root: &'a TreePointer<K, V, A>,
+        hash: u64,
+        alloc: A,
+    ) -> Self {
+        let mut cursor = Cursor::ghost(root, alloc);
+        let mut found = 0;
+        let mut tree_ptr = root;
+        while let TreePointer::NonEmpty(ref node) = *tree_ptr {
+            cursor.path.push(node.as_ref());
+            if node.hash >= hash {
+                // Everything before the node in hash order is on the left
+                found = cursor.path.len();
+                tree_ptr = &node.left;
+            } else {
+                tree_ptr = &node.right;
+            }
+        }
+        cursor.path.truncate(found);
+        cursor
+    }
+
+    /// Get the current pair, or None at the ghost position.
+    pub fn key_value(&self) -> Option<(&'a K, &'a V)> {
+        let node = self.path.last()?;
+        Some((&node.key, &node.value))
+    }
+
+    /// Current node, or None at the ghost position.
+    pub(super) fn node(&self) -> Option<&'a TreeNode<K, V, A>> {
+        self.path.last().copied()
+    }
+
+    /// Move to the next pair in hash order. From the last pair the cursor
+    /// moves to the ghost position, and from it to the first pair.
+    pub fn move_next(&mut self) {
+        let right = match self.path.last() {
+            None => self.root,
+            Some(node) => &node.right,
+        };
+        if right.is_non_empty() {
+            self.push_edge(right, Side::Left);
+            return;
+        }
+        // Climb, until we come from a left child
+        while let Some(child) = self.path.pop() {
+            match self.path.last() {
+                Some(parent) if side_of(parent, child) == Side::Left => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Move to the previous pair in hash order. From the first pair the
+    /// cursor moves to the ghost position, and from it to the last pair.
+    pub fn move_prev(&mut self) {
+        let left = match self.path.last() {
+            None => self.root,
+            Some(node) => &node.left,
+        };
+        if left.is_non_empty() {
+            self.push_edge(left, Side::Right);
+            return;
+        }
+        // Climb, until we come from a right child
+        while let Some(child) = self.path.pop() {
+            match self.path.last() {
+                Some(parent) if side_of(parent, child) == Side::Right => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Get the next pair without moving, or None if there is no pair after
+    /// the current one. At the ghost position it is the first pair.
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        let node = self.neighbour(Side::Right)?;
+        Some((&node.key, &node.value))
+    }
+
+    /// Get the previous pair without moving, or None if there is no pair
+    /// before the current one. At the ghost position it is the last pair.
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        let node = self.neighbour(Side::Left)?;
+        Some((&node.key, &node.value))
+    }
+
+    /// Push `tree_ptr` and its descendants on the `side` edge.
+    fn push_edge(
+        &mut self,
+        mut tree_ptr: &'a TreePointer<K, V, A>,
+        side: Side,
+    ) {
+        while let TreePointer::NonEmpty(ref node) = *tree_ptr {
+            self.path.push(node.as_ref());
+            tree_ptr = match side {
+                Side::Left => &node.left,
+                Side::Right => &node.right,
+            };
+        }
+    }
+
+    /// Next node on the `side` in hash order, without moving.
+    fn neighbour(&self, side: Side) -> Option<&'a TreeNode<K, V, A>> {
+        let child_ptr = match self.path.last() {
+            None => self.root,
+            Some(node) => node.child(side),
+        };
+        if let TreePointer::NonEmpty(ref node) = *child_ptr {
+            // The closest node of the subtree is on its opposite edge
+            let mut node = node.as_ref();
+            while let TreePointer::NonEmpty(ref next) = *node.child(!side) {
+                node = next.as_ref();
+            }
+            return Some(node);
+        }
+        // The closest ancestor, which has the current node on its `!side`
+        self.path
+            .windows(2)
+            .rev()
+            .find(|pair| side_of(pair[0], pair[1]) == !side)
+            .map(|pair| pair[0])
+    }
+
+    /// Sides of turns from the root to the current node.
+    fn sides(&self) -> Option<AllocVec<Side, A>>
+    where
+        A: Clone,
+    {
+        if self.path.is_empty() {
+            return None;
+        }
+        let mut sides = AllocVec::new_in(self.path.allocator().clone());
+        for pair in self.path.windows(2) {
+            sides.push(side_of(pair[0], pair[1]));
+        }
+        Some(sides)
+    }
+}
+
+impl<'a, K, V, A: Allocator + Clone> Clone for Cursor<'a, K, V, A> {
+    fn clone(&self) -> Self {
+        Cursor {
+            root: self.root,
+            path: self.path.clone(),
+        }
+    }
+}
+
+// ───── CursorMut ────────────────────────────────────────────────────────── //
+
+/// Cursor over a `HashTree`, which can change values, remove the current
+/// pair and insert new pairs around it.
+///
+/// Every access, peek and move follows sides of turns from the root to the
+/// current node, so it takes O(height), but without any comparisons of
+/// hashes or keys. Moves reuse the buffer of sides, so they don't allocate
+/// unless the cursor goes deeper than before.
+pub struct CursorMut<'a, K, V, S, A: Allocator + Clone = Global> {
+    tree: &'a mut HashTree<K, V, S, A>,
+    /// Sides of turns from the root to the current node.
+    sides: AllocVec<Side, A>,
+    /// Whether the cursor is at the ghost position, then `sides` is unused.
+    ghost: bool,
+}
+
+impl<'a, K, V, S, A> CursorMut<'a, K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    fn new(
+        tree: &'a mut HashTree<K, V, S, A>,
+        sides: Option<AllocVec<Side, A>>,
+    ) -> Self {
+        let ghost = sides.is_none();
+        let sides =
+            sides.unwrap_or_else(|| AllocVec::new_in(tree.alloc.clone()));
+        CursorMut { tree, sides, ghost }
+    }
+
+    /// Get read-only cursor at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, A> {
+        let mut cursor =
+            Cursor::ghost(&self.tree.root, self.tree.alloc.clone());
+        if !self.ghost {
+            let mut node = self.tree.root.as_ref().as_ref();
+            cursor.path.push(node);
+            for &side in self.sides.iter() {
+                node = node.child(side).as_ref();
+                cursor.path.push(node);
+            }
+        }
+        cursor
+    }
+
+    /// Get the current pair, or None at the ghost position.
+    pub fn key_value(&self) -> Option<(&K, &V)> {
+        let node = self.current()?;
+        Some((&node.key, &node.value))
+    }
+
+    /// Get mutable reference to the current value, or None at the ghost
+    /// position.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        if self.ghost {
+            return None;
+        }
+        Some(&mut self.pointer_mut().as_mut().value)
+    }
+
+    /// Same as `Cursor::move_next`.
+    pub fn move_next(&mut self) {
+        self.step(Side::Right);
+    }
+
+    /// Same as `Cursor::move_prev`.
+    pub fn move_prev(&mut self) {
+        self.step(Side::Left);
+    }
+
+    /// Same as `Cursor::peek_next`.
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        self.neighbour(Side::Right)
+            .map(|node| (&node.key, &node.value))
+    }
+
+    /// Same as `Cursor::peek_prev`.
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        self.neighbour(Side::Left)
+            .map(|node| (&node.key, &node.value))
+    }
+
+    /// Remove the current pair and move to the next one. Returns None and
+    /// does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.ghost {
+            return None;
+        }
+        let sides = &mut self.sides;
+        let pointer = follow_mut(&mut self.tree.root, sides);
+        let node = pointer.as_mut();
+
+        let removed =
+            match (node.left.is_non_empty(), node.right.is_non_empty()) {
+                (true, true) => {
+                    // The next node moves into the current one, so the cursor
+                    // stays in place
+                    let (mut key, mut hash, mut value) =
+                        node.right.extract_min().unwrap();
+                    core::mem::swap(&mut node.key, &mut key);
+                    core::mem::swap(&mut node.hash, &mut hash);
+                    core::mem::swap(&mut node.value, &mut value);
+                    (key, value)
+                }
+                (false, true) => {
+                    // The next node is the leftmost one of the right subtree,
+                    // which takes the place of the current node
+                    let right = node.right.take();
+                    let node = Box::into_inner(pointer.replace(right).unwrap());
+                    let mut next = pointer.as_ref();
+                    while let TreePointer::NonEmpty(ref left) = next.left {
+                        sides.push(Side::Left);
+                        next = left;
+                    }
+                    (node.key, node.value)
+                }
+                (_, false) => {
+                    let left = node.left.take();
+                    let node = Box::into_inner(pointer.replace(left).unwrap());
+                    // The next node is the closest ancestor on the right
+                    match sides.iter().rposition(|&side| side == Side::Left) {
+                        Some(depth) => sides.truncate(depth),
+                        None => self.ghost = true,
+                    }
+                    (node.key, node.value)
+                }
+            };
+        self.tree.check_invariants();
+        Some(removed)
+    }
+
+    /// Insert a pair right before the current one, or as the last pair at
+    /// the ghost position. The cursor doesn't move.
+    ///
+    /// Fails, and gives the pair back, if `key` is present, or its hash is
+    /// out of order between the previous and the current pairs.
+    pub fn insert_before(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), UnorderedKeyError<K, V>> {
+        self.insert_at(key, value, Side::Left)
+    }
+
+    /// Insert a pair right after the current one, or as the first pair at
+    /// the ghost position. The cursor doesn't move.
+    ///
+    /// Fails, and gives the pair back, if `key` is present, or its hash is
+    /// out of order between the current and the next pairs.
+    pub fn insert_after(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(), UnorderedKeyError<K, V>> {
+        self.insert_at(key, value, Side::Right)
+    }
+
+    /// Insert a pair next to the current one on `side`.
+    fn insert_at(
+        &mut self,
+        key: K,
+        value: V,
+        side: Side,
+    ) -> Result<(), UnorderedKeyError<K, V>> {
+        let hash = self.tree.state.hash_one(&key);
+        let present = self.tree.root.find(hash, &mut |k| *k == key);
+        let current = self.current().map(|node| node.hash);
+        let neighbour = self.neighbour(side).map(|node| node.hash);
+        let (prev, next) = match side {
+            Side::Left => (neighbour, current),
+            Side::Right => (current, neighbour),
+        };
+        let unordered = matches!(prev, Some(prev) if prev > hash)
+            || matches!(next, Some(next) if hash > next);
+        if present.is_non_empty() || unordered {
+            return Err(UnorderedKeyError { key, value });
+        }
+
+        // The place is the `side` child of the current node, if it is
+        // empty, or the opposite edge of that subtree. At the ghost position
+        // it is the opposite edge of the whole tree.
+        let alloc = self.tree.alloc.clone();
+        let mut pointer = if self.ghost {
+            &mut self.tree.root
+        } else {
+            follow_mut(&mut self.tree.root, &self.sides)
+                .as_mut()
+                .child_mut(side)
+        };
+        while pointer.is_non_empty() {
+            pointer = pointer.as_mut().child_mut(!side);
+        }
+        *pointer = TreePointer::new(
+            TreePointer::Empty,
+            key,
+            hash,
+            value,
+            TreePointer::Empty,
+            alloc,
+        );
+        self.tree.check_invariants();
+        Ok(())
+    }
+
+    fn current(&self) -> Option<&TreeNode<K, V, A>> {
+        if self.ghost {
+            return None;
+        }
+        Some(follow(&self.tree.root, &self.sides).as_ref().as_ref())
+    }
+
+    /// Same as `Cursor::neighbour`.
+    fn neighbour(&self, side: Side) -> Option<&TreeNode<K, V, A>> {
+        let child_ptr = match self.current() {
+            None => &self.tree.root,
+            Some(node) => node.child(side),
+        };
+        if let TreePointer::NonEmpty(ref node) = *child_ptr {
+            // The closest node of the subtree is on its opposite edge
+            let mut node = node.as_ref();
+            while let TreePointer::NonEmpty(ref next) = *node.child(!side) {
+                node = next.as_ref();
+            }
+            return Some(node);
+        }
+        if self.ghost {
+            return None;
+        }
+        // The closest ancestor, which has the current node on its `!side`
+        let depth = self.sides.iter().rposition(|&turn| turn == !side)?;
+        Some(
+            follow(&self.tree.root, &self.sides[..depth])
+                .as_ref()
+                .as_ref(),
+        )
+    }
+
+    /// Move to the next node on `side` in hash order, like `Cursor` does,
+    /// but by changing `sides` in place.
+    fn step(&mut self, side: Side) {
+        let child_ptr = if self.ghost {
+            self.sides.clear();
+            &self.tree.root
+        } else {
+            follow(&self.tree.root, &self.sides).as_ref().child(side)
+        };
+        if let TreePointer::NonEmpty(ref node) = *child_ptr {
+            if !self.ghost {
+                self.sides.push(side);
+            }
+            self.ghost = false;
+            // The closest node of the subtree is on its opposite edge
+            let mut node = node.as_ref();
+            while let TreePointer::NonEmpty(ref next) = *node.child(!side) {
+                self.sides.push(!side);
+                node = next.as_ref();
+            }
+        } else if !self.ghost {
+            // The closest ancestor, which has the current node on its `!side`
+            match self.sides.iter().rposition(|&turn| turn == !side) {
+                Some(depth) => self.sides.truncate(depth),
+                None => self.ghost = true,
+            }
+        }
+    }
+
+    fn pointer_mut(&mut self) -> &mut TreePointer<K, V, A> {
+        if self.ghost {
+            &mut self.tree.root
+        } else {
+            follow_mut(&mut self.tree.root, &self.sides)
+        }
+    }
+}
+
+// ───── Helpers ──────────────────────────────────────────────────────────── //
+
+/// Side of `parent`, where `child` is.
+fn side_of<K, V, A: Allocator>(
+    parent: &TreeNode<K, V, A>,
+    child: &TreeNode<K, V, A>,
+) -> Side {
+    match parent.left {
+        TreePointer::NonEmpty(ref left) if core::ptr::eq(&**left, child) => {
+            Side::Left
+        }
+        _ => Side::Right,
+    }
+}
+
+/// Pointer, reached from `root` by `sides`.
+fn follow<'a, K, V, A: Allocator>(
+    root: &'a TreePointer<K, V, A>,
+    sides: &[Side],
+) -> &'a TreePointer<K, V, A> {
+    let mut pointer = root;
+    for &side in sides {
+        pointer = pointer.as_ref().child(side);
+    }
+    pointer
+}
+
+/// Same as `follow`, but gives a mutable pointer.
+fn follow_mut<'a, K, V, A: Allocator>(
+    root: &'a mut TreePointer<K, V, A>,
+    sides: &[Side],
+) -> &'a mut TreePointer<K, V, A> {
+    let mut pointer = root;
+    for &side in sides {
+        pointer = pointer.as_mut().child_mut(side);
+    }
+    pointer
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use core::hash::BuildHasher;
+
+    use super::*;
+    use crate::hash_tree::model_tests::CollidingState;
+
+    fn create_tree(len: u32) -> HashTree<u32, u32> {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..len {
+            tree.insert(key, key);
+        }
+        tree
+    }
+
+    fn keys(tree: &HashTree<u32, u32>) -> Vec<u32> {
+        tree.into_iter().map(|(&key, _)| key).collect()
+    }
+
+    #[test]
+    fn test_cursor_moves() {
+        let tree = create_tree(100);
+        let expected = keys(&tree);
+
+        let mut forward = Vec::new();
+        let mut cursor = tree.cursor_front();
+        while let Some((&key, _)) = cursor.key_value() {
+            assert_eq!(
+                cursor.peek_prev().map(|(&k, _)| k),
+                forward.last().copied()
+            );
+            forward.push(key);
+            let next = cursor.peek_next();
+            cursor.move_next();
+            assert_eq!(next, cursor.key_value());
+        }
+        assert_eq!(forward, expected);
+
+        let mut backward = Vec::new();
+        let mut cursor = tree.cursor_back();
+        while let Some((&key, _)) = cursor.key_value() {
+            backward.push(key);
+            let prev = cursor.peek_prev();
+            cursor.move_prev();
+            assert_eq!(prev, cursor.key_value());
+        }
+        backward.reverse();
+        assert_eq!(backward, expected);
+
+        // Moves wrap around the ghost position
+        cursor.move_prev();
+        assert_eq!(cursor.key_value(), tree.cursor_back().key_value());
+        cursor.move_next();
+        assert_eq!(cursor.key_value(), None);
+        cursor.move_next();
+        assert_eq!(cursor.key_value(), tree.cursor_front().key_value());
+
+        let empty = create_tree(0);
+        assert_eq!(empty.cursor_front().key_value(), None);
+        assert_eq!(empty.cursor_back().peek_next(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut_moves() {
+        let mut tree = create_tree(100);
+        let expected = keys(&tree);
+        let key = |pair: Option<(&u32, &u32)>| pair.map(|(&key, _)| key);
+
+        let mut cursor = tree.cursor_back_mut();
+        cursor.move_next();
+        assert_eq!(cursor.key_value(), None);
+        // Moves wrap around the ghost position
+        for _ in 0..2 {
+            for (index, &next) in expected.iter().enumerate() {
+                assert_eq!(key(cursor.peek_next()), Some(next));
+                cursor.move_next();
+                assert_eq!(key(cursor.key_value()), Some(next));
+                let prev = index.checked_sub(1).map(|index| expected[index]);
+                assert_eq!(key(cursor.peek_prev()), prev);
+            }
+            cursor.move_next();
+            assert_eq!(cursor.key_value(), None);
+        }
+        for &prev in expected.iter().rev() {
+            assert_eq!(key(cursor.peek_prev()), Some(prev));
+            cursor.move_prev();
+            assert_eq!(key(cursor.key_value()), Some(prev));
+        }
+        cursor.move_prev();
+        assert_eq!(cursor.key_value(), None);
+    }
+
+    #[test]
+    fn test_lower_bound_hash() {
+        let tree = create_tree(100);
+        let hashes: Vec<u64> = tree
+            .into_iter()
+            .map(|(key, _)| tree.hasher().hash_one(key))
+            .collect();
+
+        for (index, &hash) in hashes.iter().enumerate() {
+            let cursor = tree.lower_bound_hash(hash);
+            assert_eq!(cursor.key_value(), tree.into_iter().nth(index));
+            let cursor = tree.lower_bound_hash(hash + 1);
+            assert_eq!(cursor.key_value(), tree.into_iter().nth(index + 1));
+        }
+        assert_eq!(
+            tree.lower_bound_hash(0).key_value(),
+            tree.cursor_front().key_value()
+        );
+        assert_eq!(tree.lower_bound_hash(u64::MAX).key_value(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut_remove() {
+        let mut tree = create_tree(100);
+        let mut expected = keys(&tree);
+
+        // Remove every other pair in one pass
+        let mut cursor = tree.cursor_front_mut();
+        while let Some((&key, _)) = cursor.key_value() {
+            let next = cursor.peek_next().map(|(&k, _)| k);
+            if key % 2 == 0 {
+                assert_eq!(cursor.remove_current(), Some((key, key)));
+                assert_eq!(cursor.key_value().map(|(&k, _)| k), next);
+            } else {
+                *cursor.value_mut().unwrap() += 1;
+                cursor.move_next();
+            }
+        }
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(cursor.value_mut(), None);
+
+        expected.retain(|key| key % 2 == 1);
+        assert_eq!(keys(&tree), expected);
+        assert!(tree.into_iter().all(|(&key, &value)| value == key + 1));
+
+        // Remove the rest from the back
+        let mut cursor = tree.cursor_back_mut();
+        while cursor.remove_current().is_some() {
+            cursor.move_prev();
+        }
+        assert_eq!(tree.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_cursor_mut_insert() {
+        let mut tree = create_tree(100);
+        let expected = keys(&tree);
+        let (first, last) = (expected[0], expected[99]);
+        tree.remove(&first);
+        tree.remove(&last);
+
+        let mut cursor = tree.cursor_front_mut();
+        let current = cursor.key_value().map(|(&k, &v)| (k, v));
+        assert_eq!(
+            cursor.insert_before(last, 0),
+            Err(UnorderedKeyError {
+                key: last,
+                value: 0
+            })
+        );
+        assert!(cursor.insert_before(expected[1], 0).is_err());
+        assert_eq!(cursor.insert_before(first, first), Ok(()));
+        assert_eq!(cursor.key_value().map(|(&k, &v)| (k, v)), current);
+        assert_eq!(cursor.peek_prev(), Some((&first, &first)));
+
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.key_value(), None);
+        assert_eq!(cursor.insert_before(last, last), Ok(()));
+        assert_eq!(keys(&tree), expected);
+
+        // Placement doesn't depend on the shape of the tree
+        for key in expected.iter().copied().step_by(7) {
+            tree.remove(&key);
+            let hash = tree.hasher().hash_one(key);
+            let mut cursor = tree.lower_bound_hash_mut(hash);
+            if key % 2 == 0 {
+                assert_eq!(cursor.insert_before(key, key), Ok(()));
+            } else {
+                cursor.move_prev();
+                assert_eq!(cursor.insert_after(key, key), Ok(()));
+            }
+        }
+        assert_eq!(keys(&tree), expected);
+    }
+
+    #[test]
+    fn test_cursor_with_collisions() {
+        let mut tree = HashTree::with_hasher(CollidingState::new(1, 0b1));
+        for key in 0..50u16 {
+            tree.insert(key, ());
+        }
+        let keys: Vec<u16> = (&tree).into_iter().map(|(&key, _)| key).collect();
+
+        // Any pair can be put back before the first pair with not less hash
+        for &key in keys.iter() {
+            tree.remove(&key);
+            let hash = tree.hasher().hash_one(key);
+            let mut cursor = tree.lower_bound_hash_mut(hash);
+            assert_eq!(cursor.insert_before(key, ()), Ok(()));
+        }
+        let mut cursor = tree.cursor_back_mut();
+        while let Some((&key, _)) = cursor.key_value() {
+            if key % 3 == 0 {
+                cursor.remove_current();
+            }
+            cursor.move_prev();
+        }
+
+        let mut expected = HashTree::with_hasher(CollidingState::new(1, 0b1));
+        for key in (0..50u16).filter(|key| key % 3 != 0) {
+            expected.insert(key, ());
+        }
+        assert_eq!(tree, expected);
+    }
+}
@@ -0,0 +1,322 @@
+//! Streaming diff of two trees. Both trees are walked once in hash order,
+//! like in a merge join, so the diff takes O(n + m) time and memory only
+//! for stacks of the two walks, and for nodes with colliding hashes. Hashes
+//! of the trees must be comparable, so the trees need equal hashers (see
+//! `HashTree::new_with_seed`).
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, TreeNode, TreeRange};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get iterator over differences, which turn this tree into `other`, in
+    /// hash order. Hashers of the trees must be equal, which is checked in
+    /// debug builds. In release builds different hashers give an incorrect
+    /// diff instead of a panic.
+    ///
+    /// ```
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut old = HashTree::new_with_seed(1);
+    /// old.insert("a", 1);
+    /// old.insert("b", 2);
+    /// let mut new = old.clone();
+    /// new.insert("b", 3);
+    /// new.insert("c", 4);
+    /// new.remove("a");
+    ///
+    /// let mut replica = old.clone();
+    /// replica.apply_diff(old.diff(&new));
+    /// assert_eq!(replica, new);
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a Self) -> Diff<'a, K, V, A>
+    where
+        V: PartialEq,
+    {
+        // Equal hashers hash any key to the same hash, and different ones
+        // hash a fixed key differently, except for a 2^-64 chance
+        debug_assert_eq!(
+            self.state.hash_one(HASHER_PROBE),
+            other.state.hash_one(HASHER_PROBE),
+            "trees have different hashers"
+        );
+        let mut old = self.range_hash(..);
+        let mut new = other.range_hash(..);
+        let (old_next, new_next) = (old.next_node(), new.next_node());
+        Diff {
+            old,
+            new,
+            old_next,
+            new_next,
+            pending: AllocVec::new_in(self.alloc.clone()),
+        }
+    }
+
+    /// Apply differences, made by `diff`: insert added and changed pairs,
+    /// and remove removed ones. Applying `old.diff(&new)` to a tree equal
+    /// to `old` makes it equal to `new`.
+    pub fn apply_diff<'a, I>(&mut self, diff: I)
+    where
+        I: IntoIterator<Item = DiffItem<'a, K, V>>,
+        K: Clone + 'a,
+        V: Clone + 'a,
+    {
+        for item in diff {
+            match item {
+                DiffItem::Added(key, value) => {
+                    self.insert(key.clone(), value.clone());
+                }
+                DiffItem::Removed(key, _) => {
+                    self.remove(key);
+                }
+                DiffItem::Changed { key, new, .. } => {
+                    self.insert(key.clone(), new.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Key, hashed by both hashers in `HashTree::diff` to compare them.
+const HASHER_PROBE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// ───── Diff ─────────────────────────────────────────────────────────────── //
+
+/// Difference between two trees, yielded by `HashTree::diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffItem<'a, K, V> {
+    /// Pair is present only in the other tree.
+    Added(&'a K, &'a V),
+    /// Pair is present only in this tree.
+    Removed(&'a K, &'a V),
+    /// Key is present in both trees, but with different values.
+    Changed {
+        /// Key, stored in this tree.
+        key: &'a K,
+        /// Value in this tree.
+        old: &'a V,
+        /// Value in the other tree.
+        new: &'a V,
+    },
+}
+
+/// Iterator over differences of two trees, returned by `HashTree::diff`.
+pub struct Diff<'a, K, V, A: Allocator + Clone> {
+    old: TreeRange<'a, K, V, A>,
+    new: TreeRange<'a, K, V, A>,
+    /// Next nodes of the walks, which are not compared yet.
+    old_next: Option<&'a TreeNode<K, V, A>>,
+    new_next: Option<&'a TreeNode<K, V, A>>,
+    /// Differences, which are not yielded yet, the next one on top. Filled
+    /// only when nodes with equal hashes have different keys.
+    pending: AllocVec<DiffItem<'a, K, V>, A>,
+}
+
+impl<'a, K, V, A> Diff<'a, K, V, A>
+where
+    K: Eq,
+    V: PartialEq,
+    A: Allocator + Clone,
+{
+    /// Compare nodes with equal hashes: `old` and `new`, and the following
+    /// nodes with the same hash. Usually there is no such nodes, but
+    /// colliding keys may come in any order.
+    fn compare_group(
+        &mut self,
+        old: &'a TreeNode<K, V, A>,
+        new: &'a TreeNode<K, V, A>,
+    ) -> Option<DiffItem<'a, K, V>> {
+        let hash = old.hash;
+        self.old_next = self.old.next_node();
+        self.new_next = self.new.next_node();
+        let is_colliding = |next: Option<&TreeNode<K, V, A>>| {
+            next.map(|node| node.hash) == Some(hash)
+        };
+
+        if !is_colliding(self.old_next) && !is_colliding(self.new_next) {
+            if old.key != new.key {
+                self.pending.push(DiffItem::Added(&new.key, &new.value));
+                return Some(DiffItem::Removed(&old.key, &old.value));
+            }
+            return changed(old, new);
+        }
+
+        let alloc = self.pending.allocator().clone();
+        let mut old_group = AllocVec::new_in(alloc.clone());
+        old_group.push(old);
+        while let Some(node) = self.old_next.filter(|node| node.hash == hash) {
+            old_group.push(node);
+            self.old_next = self.old.next_node();
+        }
+        let mut new_group = AllocVec::new_in(alloc);
+        new_group.push(new);
+        while let Some(node) = self.new_next.filter(|node| node.hash == hash) {
+            new_group.push(node);
+            self.new_next = self.new.next_node();
+        }
+
+        // `pending` is a stack, so differences are pushed in reverse order
+        for new in new_group.iter().rev() {
+            if !old_group.iter().any(|old| old.key == new.key) {
+                self.pending.push(DiffItem::Added(&new.key, &new.value));
+            }
+        }
+        for old in old_group.iter().rev() {
+            let item = match new_group.iter().find(|new| new.key == old.key) {
+                Some(new) => changed(old, new),
+                None => Some(DiffItem::Removed(&old.key, &old.value)),
+            };
+            self.pending.extend(item);
+        }
+        self.pending.pop()
+    }
+}
+
+impl<'a, K, V, A> Iterator for Diff<'a, K, V, A>
+where
+    K: Eq,
+    V: PartialEq,
+    A: Allocator + Clone,
+{
+    type Item = DiffItem<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use core::cmp::Ordering::*;
+
+        loop {
+            if let Some(item) = self.pending.pop() {
+                return Some(item);
+            }
+            // Missing node of a finished walk is greater than any other
+            let order = match (self.old_next, self.new_next) {
+                (None, None) => return None,
+                (Some(_), None) => Less,
+                (None, Some(_)) => Greater,
+                (Some(old), Some(new)) => old.hash.cmp(&new.hash),
+            };
+            match order {
+                Less => {
+                    let old = self.old_next.take()?;
+                    self.old_next = self.old.next_node();
+                    return Some(DiffItem::Removed(&old.key, &old.value));
+                }
+                Greater => {
+                    let new = self.new_next.take()?;
+                    self.new_next = self.new.next_node();
+                    return Some(DiffItem::Added(&new.key, &new.value));
+                }
+                Equal => {
+                    let (old, new) = (self.old_next?, self.new_next?);
+                    if let Some(item) = self.compare_group(old, new) {
+                        return Some(item);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `Changed` item for nodes with equal keys, if their values differ.
+fn changed<'a, K, V: PartialEq, A: Allocator>(
+    old: &'a TreeNode<K, V, A>,
+    new: &'a TreeNode<K, V, A>,
+) -> Option<DiffItem<'a, K, V>> {
+    (old.value != new.value).then_some(DiffItem::Changed {
+        key: &old.key,
+        old: &old.value,
+        new: &new.value,
+    })
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::model_tests::CollidingState;
+
+    fn create_tree(keys: core::ops::Range<u32>) -> HashTree<u32, u32> {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in keys {
+            tree.insert(key, key);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = create_tree(0..100);
+        let mut new = create_tree(50..150);
+        new.insert(60, 0);
+
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut changed = Vec::new();
+        for item in old.diff(&new) {
+            match item {
+                DiffItem::Added(&key, _) => added.push(key),
+                DiffItem::Removed(&key, _) => removed.push(key),
+                DiffItem::Changed { key, old, new } => {
+                    changed.push((*key, *old, *new))
+                }
+            }
+        }
+        added.sort_unstable();
+        removed.sort_unstable();
+
+        assert_eq!(added, (100..150).collect::<Vec<_>>());
+        assert_eq!(removed, (0..50).collect::<Vec<_>>());
+        assert_eq!(changed, [(60, 60, 0)]);
+        assert_eq!(old.diff(&old).count(), 0);
+    }
+
+    #[test]
+    fn test_apply_diff() {
+        let old = create_tree(0..100);
+        let mut new = create_tree(50..150);
+        new.insert(60, 0);
+
+        let mut replica = old.clone();
+        replica.apply_diff(old.diff(&new));
+        assert_eq!(replica, new);
+
+        let mut replica = new.clone();
+        replica.apply_diff(new.diff(&old));
+        assert_eq!(replica, old);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "trees have different hashers")]
+    fn test_diff_different_hashers() {
+        // Empty trees have no keys to check, but their hashers still differ
+        let old: HashTree<u32, u32> = HashTree::new_with_seed(1);
+        let new: HashTree<u32, u32> = HashTree::new_with_seed(2);
+        old.diff(&new).count();
+    }
+
+    #[test]
+    fn test_diff_with_collisions() {
+        let mut old = HashTree::with_hasher(CollidingState::new(1, 0b11));
+        let mut new = HashTree::with_hasher(CollidingState::new(1, 0b11));
+        for key in 0..100u16 {
+            old.insert(key, u32::from(key));
+            new.insert(key + 30, u32::from(key + 30));
+        }
+        new.insert(50, 0);
+
+        let mut replica = HashTree::with_hasher(CollidingState::new(1, 0b11));
+        replica.bulk_load((0..100u16).map(|key| (key, u32::from(key))));
+        replica.apply_diff(old.diff(&new));
+        assert_eq!(replica, new);
+        assert_eq!(old.diff(&new).count(), 30 + 30 + 1);
+    }
+}
@@ -0,0 +1,497 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Write};
+
+use allocator_api2::alloc::Allocator;
+
+use super::{HashTree, TreeNode, TreePointer};
+
+// ───── Layout ───────────────────────────────────────────────────────────── //
+
+/// Side of the parent, on which child is placed.
+#[derive(Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn name(self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+enum VertexKind<'a, K, V, A: Allocator> {
+    Node(&'a TreeNode<K, V, A>),
+    Empty,
+}
+
+struct Vertex<'a, K, V, A: Allocator> {
+    id: usize,
+    /// Nodes and empty pointers interleave in hash order, so the node with
+    /// in-order rank `i` lives in column `2 * i + 1` and empty pointers fill
+    /// even columns.
+    column: usize,
+    depth: usize,
+    kind: VertexKind<'a, K, V, A>,
+}
+
+struct Edge {
+    from: usize,
+    to: usize,
+    side: Side,
+}
+
+/// Flat description of the tree, shared by all export formats.
+struct Layout<'a, K, V, A: Allocator> {
+    vertices: Vec<Vertex<'a, K, V, A>>,
+    edges: Vec<Edge>,
+    /// Count of nodes, which were already visited in hash order.
+    rank: usize,
+    next_id: usize,
+}
+
+impl<'a, K, V, A: Allocator> Layout<'a, K, V, A> {
+    fn new(root: &'a TreePointer<K, V, A>) -> Self {
+        let mut layout = Layout {
+            vertices: Vec::new(),
+            edges: Vec::new(),
+            rank: 0,
+            next_id: 0,
+        };
+
+        // Same walk as in `TreeIter`, but every node remembers its depth and
+        // id, so we can connect children with their parents.
+        let mut unvisited = Vec::new();
+        layout.push_left_edge(&mut unvisited, root, 0, None);
+
+        while let Some((node, depth, id)) = unvisited.pop() {
+            layout.vertices.push(Vertex {
+                id,
+                column: 2 * layout.rank + 1,
+                depth,
+                kind: VertexKind::Node(node),
+            });
+            layout.rank += 1;
+            layout.push_left_edge(
+                &mut unvisited,
+                &node.right,
+                depth + 1,
+                Some((id, Side::Right)),
+            );
+        }
+        layout
+    }
+
+    fn push_left_edge(
+        &mut self,
+        unvisited: &mut Vec<(&'a TreeNode<K, V, A>, usize, usize)>,
+        mut tree_ptr: &'a TreePointer<K, V, A>,
+        mut depth: usize,
+        mut parent: Option<(usize, Side)>,
+    ) {
+        while let TreePointer::NonEmpty(ref node) = *tree_ptr {
+            let id = self.connect(parent);
+            unvisited.push((node.as_ref(), depth, id));
+            parent = Some((id, Side::Left));
+            tree_ptr = &node.left;
+            depth += 1;
+        }
+
+        // Empty child of the last node. It is placed right before the node,
+        // which will be visited next.
+        if parent.is_some() {
+            let id = self.connect(parent);
+            self.vertices.push(Vertex {
+                id,
+                column: 2 * self.rank,
+                depth,
+                kind: VertexKind::Empty,
+            });
+        }
+    }
+
+    /// Allocate id for a new vertex and connect it to the `parent`.
+    fn connect(&mut self, parent: Option<(usize, Side)>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some((from, side)) = parent {
+            self.edges.push(Edge { from, to: id, side });
+        }
+        id
+    }
+}
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A: Allocator> HashTree<K, V, S, A> {
+    /// Render the structure of the tree in Graphviz DOT format.
+    ///
+    /// Every node is labelled with its key, value and hash, every edge with
+    /// the side of the child, and empty children are drawn as points.
+    pub fn to_dot(&self) -> String
+    where
+        K: Debug,
+        V: Debug,
+    {
+        self.to_dot_with(|key| format!("{:?}", key), |v| format!("{:?}", v))
+    }
+
+    /// Same as `to_dot`, but keys and values are formatted by the user.
+    pub fn to_dot_with<FK, FV>(
+        &self,
+        mut key_fmt: FK,
+        mut value_fmt: FV,
+    ) -> String
+    where
+        FK: FnMut(&K) -> String,
+        FV: FnMut(&V) -> String,
+    {
+        let layout = Layout::new(&self.root);
+        let mut dot = String::from("digraph HashTree {\n");
+        dot.push_str("    node [shape=box, fontname=\"JetBrains Mono\"];\n");
+
+        for vertex in &layout.vertices {
+            match vertex.kind {
+                VertexKind::Node(node) => {
+                    let label = format!(
+                        "key: {}\nvalue: {}\nhash: {}",
+                        key_fmt(&node.key),
+                        value_fmt(&node.value),
+                        node.hash
+                    );
+                    let _ = writeln!(
+                        dot,
+                        "    n{} [label=\"{}\"];",
+                        vertex.id,
+                        escape_dot(&label)
+                    );
+                }
+                VertexKind::Empty => {
+                    let _ = writeln!(
+                        dot,
+                        "    n{} [label=\"Empty\", shape=point];",
+                        vertex.id
+                    );
+                }
+            }
+        }
+        for edge in &layout.edges {
+            let _ = writeln!(
+                dot,
+                "    n{} -> n{} [label=\"{}\"];",
+                edge.from,
+                edge.to,
+                edge.side.name()
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render the structure of the tree as a draw.io diagram, like the one in
+    /// the `diagrams` folder.
+    ///
+    /// Nodes are placed in columns by hash order and in rows by depth.
+    pub fn to_drawio(&self) -> String
+    where
+        K: Debug,
+        V: Debug,
+    {
+        self.to_drawio_with(
+            |key| format!("{:?}", key),
+            |value| format!("{:?}", value),
+        )
+    }
+
+    /// Same as `to_drawio`, but keys and values are formatted by the user.
+    pub fn to_drawio_with<FK, FV>(
+        &self,
+        mut key_fmt: FK,
+        mut value_fmt: FV,
+    ) -> String
+    where
+        FK: FnMut(&K) -> String,
+        FV: FnMut(&V) -> String,
+    {
+        const COLUMN_WIDTH: usize = 200;
+        const ROW_HEIGHT: usize = 120;
+        const NODE_WIDTH: usize = 180;
+        const NODE_HEIGHT: usize = 60;
+        const EMPTY_SIZE: usize = 20;
+        const NODE_STYLE: &str = "rounded=1;whiteSpace=wrap;html=1;\
+            strokeWidth=2;fontFamily=JetBrains Mono;";
+        const EMPTY_STYLE: &str = "ellipse;fillColor=#000000;html=1;";
+        const EDGE_STYLE: &str = "endArrow=classic;html=1;curved=1;\
+            fontFamily=JetBrains Mono;";
+
+        let layout = Layout::new(&self.root);
+        let mut xml = String::from(
+            "<mxfile host=\"ghashy_collections\">\n  \
+             <diagram name=\"HashTree\" id=\"hash_tree\">\n    \
+             <mxGraphModel grid=\"0\">\n      <root>\n        \
+             <mxCell id=\"0\" />\n        <mxCell id=\"1\" parent=\"0\" />\n",
+        );
+
+        for vertex in &layout.vertices {
+            let center_x = vertex.column * COLUMN_WIDTH / 2 + NODE_WIDTH / 2;
+            let center_y = vertex.depth * ROW_HEIGHT + NODE_HEIGHT / 2;
+            let (value, style, width, height) = match vertex.kind {
+                VertexKind::Node(node) => {
+                    let label = format!(
+                        "key: {}<br>value: {}<br>hash: {}",
+                        escape_xml(&key_fmt(&node.key)),
+                        escape_xml(&value_fmt(&node.value)),
+                        node.hash
+                    );
+                    (label, NODE_STYLE, NODE_WIDTH, NODE_HEIGHT)
+                }
+                VertexKind::Empty => {
+                    (String::new(), EMPTY_STYLE, EMPTY_SIZE, EMPTY_SIZE)
+                }
+            };
+            let _ = write!(
+                xml,
+                "        <mxCell id=\"n{}\" value=\"{}\" style=\"{}\" \
+                 vertex=\"1\" parent=\"1\">\n          \
+                 <mxGeometry x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                 as=\"geometry\" />\n        </mxCell>\n",
+                vertex.id,
+                escape_xml(&value),
+                style,
+                center_x - width / 2,
+                center_y - height / 2,
+                width,
+                height
+            );
+        }
+        for edge in &layout.edges {
+            let _ = write!(
+                xml,
+                "        <mxCell id=\"e{}\" value=\"{}\" style=\"{}\" \
+                 edge=\"1\" parent=\"1\" source=\"n{}\" \
+                 target=\"n{}\">\n          \
+                 <mxGeometry relative=\"1\" as=\"geometry\" />\n        \
+                 </mxCell>\n",
+                edge.to,
+                edge.side.name(),
+                EDGE_STYLE,
+                edge.from,
+                edge.to
+            );
+        }
+
+        xml.push_str(
+            "      </root>\n    </mxGraphModel>\n  </diagram>\n</mxfile>\n",
+        );
+        xml
+    }
+
+    /// Render the branch structure of the tree, one node per line, for
+    /// example:
+    ///
+    /// ```text
+    /// 1 => 10.0 (hash: 3458…)
+    /// ├── left: 2 => 20.0 (hash: 1527…)
+    /// └── right: 3 => 30.0 (hash: 5031…)
+    ///     ├── left: Empty
+    ///     └── right: 4 => 40.0 (hash: 9834…)
+    /// ```
+    pub fn display_tree(&self) -> String
+    where
+        K: Debug,
+        V: Debug,
+    {
+        fn node_line<K: Debug, V: Debug, A: Allocator>(
+            node: &TreeNode<K, V, A>,
+        ) -> String {
+            format!("{:?} => {:?} (hash: {})", node.key, node.value, node.hash)
+        }
+
+        let root = match self.root {
+            TreePointer::Empty => return String::from("Empty\n"),
+            TreePointer::NonEmpty(ref node) => node,
+        };
+        let mut out = node_line(root);
+        out.push('\n');
+
+        // Pre-order walk. Right child is pushed first, so left child is
+        // printed first. Leaves don't show their empty children.
+        let mut unvisited = Vec::new();
+        if root.left.is_non_empty() || root.right.is_non_empty() {
+            unvisited.push((&root.right, String::new(), Side::Right, true));
+            unvisited.push((&root.left, String::new(), Side::Left, false));
+        }
+
+        while let Some((tree_ptr, prefix, side, is_last)) = unvisited.pop() {
+            let branch = if is_last { "└── " } else { "├── " };
+            let node = match *tree_ptr {
+                TreePointer::Empty => {
+                    let _ = writeln!(
+                        out,
+                        "{}{}{}: Empty",
+                        prefix,
+                        branch,
+                        side.name()
+                    );
+                    continue;
+                }
+                TreePointer::NonEmpty(ref node) => node,
+            };
+            let _ = writeln!(
+                out,
+                "{}{}{}: {}",
+                prefix,
+                branch,
+                side.name(),
+                node_line(node)
+            );
+
+            if node.left.is_non_empty() || node.right.is_non_empty() {
+                let indent = if is_last { "    " } else { "│   " };
+                let prefix = format!("{}{}", prefix, indent);
+                unvisited.push((
+                    &node.right,
+                    prefix.clone(),
+                    Side::Right,
+                    true,
+                ));
+                unvisited.push((&node.left, prefix, Side::Left, false));
+            }
+        }
+        out
+    }
+
+    /// Print `display_tree` to stdout.
+    #[cfg(feature = "std")]
+    pub fn pretty_print(&self)
+    where
+        K: Debug,
+        V: Debug,
+    {
+        print!("{}", self.display_tree());
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_tree() -> HashTree<u32, f32> {
+        // Same tree as in `test_hash_tree_creation`
+        let mut tree = HashTree::new_with_seed(1);
+        tree.insert(1, 10.);
+        tree.insert(2, 20.);
+        tree.insert(3, 30.);
+        tree.insert(4, 40.);
+        tree.insert(5, 50.);
+        tree
+    }
+
+    #[test]
+    fn test_layout() {
+        let tree = create_tree();
+        let layout = Layout::new(&tree.root);
+
+        // 5 nodes and 6 empty pointers, every vertex except root has a parent
+        assert_eq!(layout.vertices.len(), 11);
+        assert_eq!(layout.edges.len(), 10);
+
+        let mut columns: Vec<_> =
+            layout.vertices.iter().map(|v| v.column).collect();
+        columns.sort_unstable();
+        assert_eq!(columns, (0..11).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let tree = create_tree();
+        let dot = tree.to_dot();
+
+        assert!(dot.starts_with("digraph HashTree {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.matches("shape=point").count(), 6);
+        assert_eq!(dot.matches("[label=\"left\"]").count(), 5);
+        assert_eq!(dot.matches("[label=\"right\"]").count(), 5);
+
+        let hash = tree.root.as_ref().hash;
+        assert!(dot.contains(&format!(
+            "n0 [label=\"key: 1\\nvalue: 10.0\\nhash: {}\"];",
+            hash
+        )));
+        assert!(dot.contains("n0 -> n1 [label=\"left\"];"));
+    }
+
+    #[test]
+    fn test_display_tree() {
+        let tree = create_tree();
+        let hash = |key: u32| tree.state.hash_one(key);
+
+        assert_eq!(
+            tree.display_tree(),
+            format!(
+                "1 => 10.0 (hash: {})\n\
+                 ├── left: 2 => 20.0 (hash: {})\n\
+                 └── right: 3 => 30.0 (hash: {})\n    \
+                     ├── left: Empty\n    \
+                     └── right: 4 => 40.0 (hash: {})\n        \
+                         ├── left: 5 => 50.0 (hash: {})\n        \
+                         └── right: Empty\n",
+                hash(1),
+                hash(2),
+                hash(3),
+                hash(4),
+                hash(5)
+            )
+        );
+
+        let empty: HashTree<u32, f32> = HashTree::new();
+        assert_eq!(empty.display_tree(), "Empty\n");
+    }
+
+    #[test]
+    fn test_to_drawio() {
+        let mut tree = HashTree::new_with_seed(1);
+        tree.insert("<key>", "\"value\"");
+        let xml = tree.to_drawio();
+
+        assert!(xml.starts_with("<mxfile"));
+        assert_eq!(xml.matches("vertex=\"1\"").count(), 3);
+        assert_eq!(xml.matches("edge=\"1\"").count(), 2);
+        assert!(xml.contains("key: &amp;quot;&amp;lt;key&amp;gt;&amp;quot;"));
+    }
+}
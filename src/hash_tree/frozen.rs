@@ -0,0 +1,374 @@
+//! `FrozenHashTree` keeps the same balanced tree as `HashTree`, but as an
+//! implicit tree in arrays (Eytzinger layout): children of slot `k` are in
+//! slots `2k` and `2k + 1`, slot 0 is unused. Top levels of the tree share
+//! a few cache lines, and descent has no pointers to chase.
+
+use alloc::vec::Vec;
+use core::ops::{Bound, RangeBounds};
+
+use allocator_api2::alloc::{Allocator, Global};
+
+use super::bulk::IntoSorted;
+use super::{Equivalent, HashTree, TreePointer};
+
+// ───── FrozenHashTree ───────────────────────────────────────────────────── //
+
+/// Immutable `HashTree`, optimized for lookups. Created by
+/// `HashTree::freeze`, and can be turned back with `thaw`.
+pub struct FrozenHashTree<K, V, S = ahash::RandomState> {
+    /// Hashes in Eytzinger order, `hashes[0]` is a placeholder.
+    hashes: Vec<u64>,
+    /// `keys[k - 1]` is the key of slot `k`.
+    keys: Vec<K>,
+    /// `values[k - 1]` is the value of slot `k`.
+    values: Vec<V>,
+    state: S,
+}
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Turn the tree into a `FrozenHashTree`. Its arrays are allocated with
+    /// the global allocator.
+    pub fn freeze(self) -> FrozenHashTree<K, V, S> {
+        let mut sorted: Vec<_> =
+            IntoSorted::new(self.root, self.alloc).map(Some).collect();
+        let len = sorted.len();
+
+        // Slots are visited in order of hashes, so they get sorted pairs
+        let mut ranks = alloc::vec![0; len + 1];
+        let mut slot = first_slot(len);
+        for rank in 0..len {
+            ranks[slot] = rank;
+            slot = next_slot(slot, len);
+        }
+
+        let mut hashes = Vec::with_capacity(len + 1);
+        let mut keys = Vec::with_capacity(len);
+        let mut values = Vec::with_capacity(len);
+        hashes.push(0);
+        for &rank in &ranks[1..] {
+            let (hash, key, value) = sorted[rank].take().unwrap();
+            hashes.push(hash);
+            keys.push(key);
+            values.push(value);
+        }
+
+        FrozenHashTree {
+            hashes,
+            keys,
+            values,
+            state: self.state,
+        }
+    }
+}
+
+impl<K, V, S> FrozenHashTree<K, V, S>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+{
+    /// Turn back into a mutable `HashTree` of the same balanced shape.
+    pub fn thaw(self) -> HashTree<K, V, S> {
+        let len = self.keys.len();
+        let mut subtrees: Vec<TreePointer<K, V>> = Vec::with_capacity(len + 1);
+        subtrees.resize_with(len + 1, || TreePointer::Empty);
+
+        // Children have greater slots, so they are built first
+        let pairs = self.keys.into_iter().zip(self.values);
+        for (slot, (key, value)) in (1..len + 1).zip(pairs).rev() {
+            let right = subtrees.get_mut(2 * slot + 1).map(TreePointer::take);
+            let left = subtrees.get_mut(2 * slot).map(TreePointer::take);
+            subtrees[slot] = TreePointer::new(
+                left.unwrap_or(TreePointer::Empty),
+                key,
+                self.hashes[slot],
+                value,
+                right.unwrap_or(TreePointer::Empty),
+                Global,
+            );
+        }
+
+        let mut tree = HashTree::with_hasher(self.state);
+        if len > 0 {
+            tree.root = subtrees[1].take();
+        }
+        tree.check_invariants();
+        tree
+    }
+
+    /// Get a reference to the `BuildHasher` of this `FrozenHashTree`.
+    pub fn hasher(&self) -> &S {
+        &self.state
+    }
+
+    /// Number of pairs.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check if there are no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Get value by key, or None if not present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.find_slot(key).map(|slot| &self.values[slot - 1])
+    }
+
+    /// Get stored key and its value, or None if not present.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.find_slot(key)
+            .map(|slot| (&self.keys[slot - 1], &self.values[slot - 1]))
+    }
+
+    /// Check if there is a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.find_slot(key).is_some()
+    }
+
+    /// Get iterator over pairs in hash order.
+    pub fn iter(&self) -> FrozenIter<'_, K, V, S> {
+        self.range_hash(..)
+    }
+
+    /// Get iterator over pairs, which key hashes are in `range`, in hash
+    /// order.
+    pub fn range_hash<R>(&self, range: R) -> FrozenIter<'_, K, V, S>
+    where
+        R: RangeBounds<u64>,
+    {
+        let slot = match range.start_bound() {
+            Bound::Included(&start) => self.lower_bound(start),
+            Bound::Excluded(&start) => match start.checked_add(1) {
+                Some(start) => self.lower_bound(start),
+                None => 0,
+            },
+            Bound::Unbounded => first_slot(self.len()),
+        };
+        FrozenIter {
+            tree: self,
+            slot,
+            end: range.end_bound().cloned(),
+        }
+    }
+
+    fn find_slot<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        let mut slot = self.lower_bound(hash);
+        // Colliding hashes are next to each other in hash order
+        while slot != 0 && self.hashes[slot] == hash {
+            if key.equivalent(&self.keys[slot - 1]) {
+                return Some(slot);
+            }
+            slot = next_slot(slot, self.len());
+        }
+        None
+    }
+
+    /// Slot of the first pair in hash order with hash not less than `hash`,
+    /// or 0, if there is no such pair. The descent has no branches, except
+    /// of the loop condition.
+    fn lower_bound(&self, hash: u64) -> usize {
+        let len = self.len();
+        let mut slot = 1;
+        while slot <= len {
+            slot = 2 * slot + usize::from(self.hashes[slot] < hash);
+        }
+        // Undo right turns after the last left turn, and the left turn
+        slot >> (slot.trailing_ones() + 1)
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for FrozenHashTree<K, V, S>
+where
+    K: core::fmt::Debug + core::hash::Hash + Eq,
+    V: core::fmt::Debug,
+    S: core::hash::BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// ───── FrozenIter ───────────────────────────────────────────────────────── //
+
+/// Iterator over pairs of `FrozenHashTree` in hash order. It needs no stack,
+/// because the next slot is computed from the current one.
+pub struct FrozenIter<'a, K, V, S> {
+    tree: &'a FrozenHashTree<K, V, S>,
+    /// Next slot to visit, 0 when iteration is finished.
+    slot: usize,
+    end: Bound<u64>,
+}
+
+impl<'a, K, V, S> Iterator for FrozenIter<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.slot;
+        if slot == 0 {
+            return None;
+        }
+        let hash = self.tree.hashes[slot];
+        let before_end = match self.end {
+            Bound::Included(end) => hash <= end,
+            Bound::Excluded(end) => hash < end,
+            Bound::Unbounded => true,
+        };
+        if !before_end {
+            self.slot = 0;
+            return None;
+        }
+
+        self.slot = next_slot(slot, self.tree.keys.len());
+        Some((&self.tree.keys[slot - 1], &self.tree.values[slot - 1]))
+    }
+}
+
+// ───── Helpers ──────────────────────────────────────────────────────────── //
+
+/// Leftmost slot of the implicit tree with `len` slots, 0 if it is empty.
+fn first_slot(len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let mut slot = 1;
+    while 2 * slot <= len {
+        slot *= 2;
+    }
+    slot
+}
+
+/// In-order successor of `slot`, 0 if it is the last one.
+fn next_slot(mut slot: usize, len: usize) -> usize {
+    if 2 * slot < len {
+        // Leftmost slot of the right subtree
+        slot = 2 * slot + 1;
+        while 2 * slot <= len {
+            slot *= 2;
+        }
+        slot
+    } else {
+        // Climb while `slot` is a right child, then once more
+        slot >> (slot.trailing_ones() + 1)
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::model_tests::CollidingState;
+    use crate::hash_tree::TreeStats;
+
+    fn create_tree(len: u32) -> HashTree<u32, u32> {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..len {
+            tree.insert(key, key * 2);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_freeze() {
+        for len in [0, 1, 2, 3, 4, 7, 8, 100, 1000] {
+            let mut tree = create_tree(len);
+            let pairs: Vec<(u32, u32)> =
+                tree.iter().map(|(&k, &v)| (k, v)).collect();
+            let frozen = tree.freeze();
+
+            assert_eq!(frozen.len(), len as usize);
+            assert!(frozen.iter().map(|(&k, &v)| (k, v)).eq(pairs));
+            for key in 0..len {
+                assert_eq!(frozen.get(&key), Some(&(key * 2)));
+                assert_eq!(
+                    frozen.get_key_value(&key),
+                    Some((&key, &(key * 2)))
+                );
+            }
+            assert!(!frozen.contains_key(&len));
+        }
+    }
+
+    #[test]
+    fn test_range_hash() {
+        let frozen = create_tree(1000).freeze();
+        let hashes: Vec<u64> = frozen
+            .iter()
+            .map(|(key, _)| frozen.hasher().hash_one(key))
+            .collect();
+        let (start, end) = (hashes[100], hashes[200]);
+
+        assert!(frozen
+            .range_hash(start..end)
+            .eq(frozen.iter().skip(100).take(100)));
+        assert_eq!(frozen.range_hash(start..=end).count(), 101);
+        assert_eq!(frozen.range_hash(..end).count(), 200);
+        assert_eq!(
+            frozen
+                .range_hash((Bound::Excluded(start), Bound::Unbounded))
+                .count(),
+            899
+        );
+        assert_eq!(frozen.range_hash(end..start).count(), 0);
+        assert_eq!(
+            frozen
+                .range_hash((Bound::Excluded(u64::MAX), Bound::Unbounded))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_thaw() {
+        for len in [0, 1, 2, 3, 100, 1000] {
+            let mut tree = create_tree(len).freeze().thaw();
+
+            let stats = tree.stats();
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(stats.len, len as usize);
+            assert_eq!(stats.height, TreeStats::optimal_height(len as usize));
+            for key in 0..len {
+                assert_eq!(tree.get(&key), Some(&(key * 2)));
+            }
+            tree.insert(len, 0);
+            assert_eq!(tree.get(&len), Some(&0));
+        }
+    }
+
+    #[test]
+    fn test_frozen_with_collisions() {
+        let mut tree = HashTree::with_hasher(CollidingState::new(1, 0b11));
+        for key in 0..100u16 {
+            tree.insert(key, u32::from(key));
+        }
+
+        let frozen = tree.freeze();
+        for key in 0..100 {
+            assert_eq!(frozen.get(&key), Some(&u32::from(key)));
+        }
+        assert!(!frozen.contains_key(&100));
+
+        let mut tree = frozen.thaw();
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.iter().count(), 100);
+    }
+}
@@ -0,0 +1,334 @@
+use alloc::vec::Vec;
+use core::ops::RangeBounds;
+
+use allocator_api2::alloc::Global;
+use hashbrown::HashTable;
+
+use super::partition::TreeRange;
+use super::{Equivalent, TreeIter, TreePointer};
+
+// ───── IndexedHashTree ──────────────────────────────────────────────────── //
+
+/// `HashTree` with an additional `hashbrown::HashTable` index, so `get` and
+/// `contains_key` take O(1), while pairs are still traversed in hash order.
+///
+/// Pairs are stored densely in a `Vec`. Both the table and the tree hold
+/// indices into it, so each pair costs one more tree node and table slot,
+/// than in a plain `HashTree`.
+pub struct IndexedHashTree<K, V, S = ahash::RandomState> {
+    /// Pairs with their hashes, without gaps.
+    entries: Vec<(u64, K, V)>,
+    /// Indices of `entries`, found by hash.
+    index: HashTable<usize>,
+    /// Indices of `entries` in hash order.
+    order: TreePointer<usize, ()>,
+    state: S,
+}
+
+impl<K, V> IndexedHashTree<K, V>
+where
+    K: core::hash::Hash + Eq,
+{
+    /// Create new empty `IndexedHashTree`.
+    pub fn new() -> Self {
+        Self::with_hasher(ahash::RandomState::new())
+    }
+
+    /// Create new empty `IndexedHashTree` with seeded hasher, like
+    /// `HashTree::new_with_seed`.
+    pub fn new_with_seed(seed: u64) -> Self {
+        Self::with_hasher(ahash::RandomState::with_seeds(
+            seed, seed, seed, seed,
+        ))
+    }
+}
+
+impl<K, V, S> IndexedHashTree<K, V, S>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+{
+    /// Create new empty `IndexedHashTree`, which will use `state` to hash
+    /// keys.
+    pub fn with_hasher(state: S) -> Self {
+        IndexedHashTree {
+            entries: Vec::new(),
+            index: HashTable::new(),
+            order: TreePointer::Empty,
+            state,
+        }
+    }
+
+    /// Get a reference to the `BuildHasher` of this `IndexedHashTree`.
+    pub fn hasher(&self) -> &S {
+        &self.state
+    }
+
+    /// Number of pairs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Insert an element. If a value is already present, the old value is
+    /// returned, otherwise None is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.state.hash_one(&key);
+
+        let entries = &self.entries;
+        if let Some(&index) = self.index.find(hash, |&i| entries[i].1 == key) {
+            return Some(core::mem::replace(&mut self.entries[index].2, value));
+        }
+
+        let index = self.entries.len();
+        self.entries.push((hash, key, value));
+        let entries = &self.entries;
+        self.index.insert_unique(hash, index, |&i| entries[i].0);
+
+        // No index matches, so `find_mut` stops on the place for a new node
+        let pointer = self.order.find_mut(hash, &mut |_| false);
+        *pointer = TreePointer::new(
+            TreePointer::Empty,
+            index,
+            hash,
+            (),
+            TreePointer::Empty,
+            Global,
+        );
+        None
+    }
+
+    /// Get value by key in O(1), or None if not present.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.find_index(key).map(|index| &self.entries[index].2)
+    }
+
+    /// Get mutable reference to the value by key in O(1), or None if not
+    /// present.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.find_index(key)?;
+        Some(&mut self.entries[index].2)
+    }
+
+    /// Check in O(1) if there is a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.find_index(key).is_some()
+    }
+
+    /// Remove pair, returns value, or None if not present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        let entries = &self.entries;
+        let index = match self
+            .index
+            .find_entry(hash, |&i| key.equivalent(&entries[i].1))
+        {
+            Ok(entry) => entry.remove().0,
+            Err(_) => return None,
+        };
+        self.order.remove(hash, &mut |&i| i == index);
+        let (_, _, value) = self.entries.swap_remove(index);
+
+        // The last pair was moved into the gap, so its index is updated
+        let moved = self.entries.len();
+        if index < moved {
+            let hash = self.entries[index].0;
+            if let Some(i) = self.index.find_mut(hash, |&i| i == moved) {
+                *i = index;
+            }
+            if let TreePointer::NonEmpty(node) =
+                self.order.find_mut(hash, &mut |&i| i == moved)
+            {
+                node.key = index;
+            }
+        }
+        Some(value)
+    }
+
+    /// Get iterator over pairs in hash order.
+    pub fn iter(&self) -> IndexedIter<'_, K, V> {
+        IndexedIter {
+            order: self.order.iter_in(Global),
+            entries: &self.entries,
+        }
+    }
+
+    /// Get iterator over pairs, which key hashes are in `range`, in hash
+    /// order. Subtrees out of `range` are skipped.
+    pub fn range_by_hash<R>(&self, range: R) -> HashRange<'_, K, V>
+    where
+        R: RangeBounds<u64>,
+    {
+        HashRange {
+            order: TreeRange::new(&self.order, range, Global),
+            entries: &self.entries,
+        }
+    }
+
+    fn find_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        let entries = &self.entries;
+        self.index
+            .find(hash, |&i| key.equivalent(&entries[i].1))
+            .copied()
+    }
+}
+
+impl<K, V> Default for IndexedHashTree<K, V>
+where
+    K: core::hash::Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for IndexedHashTree<K, V, S>
+where
+    K: core::fmt::Debug + core::hash::Hash + Eq,
+    V: core::fmt::Debug,
+    S: core::hash::BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// ───── Iterators ────────────────────────────────────────────────────────── //
+
+/// Iterator over pairs of `IndexedHashTree` in hash order.
+pub struct IndexedIter<'a, K, V> {
+    order: TreeIter<'a, usize, ()>,
+    entries: &'a [(u64, K, V)],
+}
+
+impl<'a, K, V> Iterator for IndexedIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&index, _) = self.order.next()?;
+        let (_, key, value) = &self.entries[index];
+        Some((key, value))
+    }
+}
+
+/// Iterator over pairs of `IndexedHashTree` with hashes in a range, returned
+/// by `range_by_hash`.
+pub struct HashRange<'a, K, V> {
+    order: TreeRange<'a, usize, (), Global>,
+    entries: &'a [(u64, K, V)],
+}
+
+impl<'a, K, V> Iterator for HashRange<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&index, _) = self.order.next()?;
+        let (_, key, value) = &self.entries[index];
+        Some((key, value))
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use core::ops::Bound;
+
+    use super::*;
+    use crate::hash_tree::HashTree;
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut tree = IndexedHashTree::new_with_seed(1);
+        for key in 0..100 {
+            assert_eq!(tree.insert(key, key * 2), None);
+        }
+        assert_eq!(tree.insert(5, 0), Some(10));
+        assert_eq!(tree.len(), 100);
+
+        // Removing from the middle moves the last pair into the gap
+        for key in (0..100).step_by(3) {
+            assert_eq!(
+                tree.remove(&key),
+                Some(if key == 5 { 0 } else { key * 2 })
+            );
+        }
+        assert_eq!(tree.remove(&0), None);
+        for key in 0..100 {
+            let expected = match key {
+                _ if key % 3 == 0 => None,
+                5 => Some(0),
+                _ => Some(key * 2),
+            };
+            assert_eq!(tree.get(&key).copied(), expected);
+            assert_eq!(tree.contains_key(&key), expected.is_some());
+        }
+
+        *tree.get_mut(&1).unwrap() = 1000;
+        assert_eq!(tree.get(&1), Some(&1000));
+    }
+
+    #[test]
+    fn test_hash_order() {
+        let mut tree = IndexedHashTree::new_with_seed(1);
+        let mut plain = HashTree::new_with_seed(1);
+        for key in 0..100 {
+            tree.insert(key, key);
+            plain.insert(key, key);
+        }
+        for key in (0..100).step_by(7) {
+            tree.remove(&key);
+            plain.remove(&key);
+        }
+
+        assert!(tree.iter().eq(plain.iter()));
+        assert_eq!(tree.len(), tree.iter().count());
+    }
+
+    #[test]
+    fn test_range_by_hash() {
+        let mut tree = IndexedHashTree::new_with_seed(1);
+        for key in 0..1000u32 {
+            tree.insert(key, ());
+        }
+        let hashes: Vec<u64> = tree
+            .iter()
+            .map(|(key, _)| tree.state.hash_one(key))
+            .collect();
+
+        let (start, end) = (hashes[100], hashes[200]);
+        assert!(tree.range_by_hash(start..end).map(|(k, _)| k).eq(tree
+            .iter()
+            .skip(100)
+            .take(100)
+            .map(|(k, _)| k)));
+        assert_eq!(tree.range_by_hash(start..=end).count(), 101);
+        assert_eq!(tree.range_by_hash(..end).count(), 200);
+        assert_eq!(
+            tree.range_by_hash((Bound::Excluded(start), Bound::Unbounded))
+                .count(),
+            899
+        );
+        assert_eq!(tree.range_by_hash(end..start).count(), 0);
+    }
+}
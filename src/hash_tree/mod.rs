@@ -10,23 +10,112 @@
     missing_docs
 )]
 
+use core::alloc::Layout;
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec as AllocVec;
+
+// ───── Submodules ───────────────────────────────────────────────────────── //
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod augmented;
+mod bulk;
+mod convert;
+mod cursor;
+mod diff;
+mod export;
+mod frozen;
+mod indexed;
+#[cfg(test)]
+mod model_tests;
+mod pagination;
+mod partition;
+mod raw;
+#[cfg(feature = "rayon")]
+mod rayon;
+mod rebalance;
+mod sample;
+mod stats;
+mod traverse;
+mod validate;
+
+#[cfg(feature = "rayon")]
+pub use self::rayon::{IntoParIter, ParIter, ParIterMut};
+pub use augmented::{
+    AugmentedHashTree, AugmentedIter, Count, Max, Min, Monoid, Sum,
+};
+pub use cursor::{Cursor, CursorMut};
+pub use diff::{Diff, DiffItem};
+pub use frozen::{FrozenHashTree, FrozenIter};
+pub use indexed::{HashRange, IndexedHashTree, IndexedIter};
+pub use pagination::ContinuationToken;
+pub use partition::{Partitions, TreeRange};
+pub use raw::{RawHashTreeApi, RawHashTreeApiMut};
+pub use sample::BottomKSketch;
+pub use stats::TreeStats;
+pub use traverse::{NodeView, TraversalOrder, Traverse};
+
+pub use crate::error::{
+    InvalidTokenError, InvariantError, OccupiedError, TryReserveError,
+    UnorderedKeyError,
+};
+pub use hashbrown::Equivalent;
+
 // ───── TreePointer && TreeNode ──────────────────────────────────────────── //
 
-struct TreeNode<K, V> {
+struct TreeNode<K, V, A: Allocator = Global> {
     hash: u64,
     key: K,
     value: V,
-    left: TreePointer<K, V>,
-    right: TreePointer<K, V>,
+    left: TreePointer<K, V, A>,
+    right: TreePointer<K, V, A>,
 }
 
-enum TreePointer<K, V> {
+enum TreePointer<K, V, A: Allocator = Global> {
     Empty,
-    NonEmpty(Box<TreeNode<K, V>>),
+    NonEmpty(Box<TreeNode<K, V, A>, A>),
+}
+
+/// Side of a node, where its child is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl core::ops::Not for Side {
+    type Output = Side;
+
+    fn not(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
 }
 
-impl<K, V> AsRef<Box<TreeNode<K, V>>> for TreePointer<K, V> {
-    fn as_ref(&self) -> &Box<TreeNode<K, V>> {
+impl<K, V, A: Allocator> TreeNode<K, V, A> {
+    fn child(&self, side: Side) -> &TreePointer<K, V, A> {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    fn child_mut(&mut self, side: Side) -> &mut TreePointer<K, V, A> {
+        match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+        }
+    }
+}
+
+impl<K, V, A: Allocator> AsRef<Box<TreeNode<K, V, A>, A>>
+    for TreePointer<K, V, A>
+{
+    fn as_ref(&self) -> &Box<TreeNode<K, V, A>, A> {
         match *self {
             TreePointer::Empty => panic!(),
             TreePointer::NonEmpty(ref node) => node,
@@ -34,8 +123,10 @@ impl<K, V> AsRef<Box<TreeNode<K, V>>> for TreePointer<K, V> {
     }
 }
 
-impl<K, V> AsMut<Box<TreeNode<K, V>>> for TreePointer<K, V> {
-    fn as_mut(&mut self) -> &mut Box<TreeNode<K, V>> {
+impl<K, V, A: Allocator> AsMut<Box<TreeNode<K, V, A>, A>>
+    for TreePointer<K, V, A>
+{
+    fn as_mut(&mut self) -> &mut Box<TreeNode<K, V, A>, A> {
         match *self {
             TreePointer::NonEmpty(ref mut node) => node,
             TreePointer::Empty => panic!(),
@@ -43,27 +134,60 @@ impl<K, V> AsMut<Box<TreeNode<K, V>>> for TreePointer<K, V> {
     }
 }
 
-impl<K, V> TreePointer<K, V> {
-    fn new(left: Self, key: K, hash: u64, value: V, right: Self) -> Self {
-        TreePointer::NonEmpty(Box::new(TreeNode {
+impl<K, V, A: Allocator> TreePointer<K, V, A> {
+    fn new(
+        left: Self,
+        key: K,
+        hash: u64,
+        value: V,
+        right: Self,
+        alloc: A,
+    ) -> Self {
+        let node = TreeNode {
             hash,
             key,
             value,
             left,
             right,
-        }))
+        };
+        TreePointer::NonEmpty(Box::new_in(node, alloc))
+    }
+
+    /// Same as `new`, but returns an error instead of aborting, if `alloc`
+    /// can't allocate the node.
+    fn try_new(
+        left: Self,
+        key: K,
+        hash: u64,
+        value: V,
+        right: Self,
+        alloc: A,
+    ) -> Result<Self, TryReserveError> {
+        let node = TreeNode {
+            hash,
+            key,
+            value,
+            left,
+            right,
+        };
+        match Box::try_new_in(node, alloc) {
+            Ok(node) => Ok(TreePointer::NonEmpty(node)),
+            Err(_) => {
+                Err(TryReserveError::new(Layout::new::<TreeNode<K, V, A>>()))
+            }
+        }
     }
 
     fn take(&mut self) -> Self {
         match self {
             TreePointer::Empty => Self::Empty,
             TreePointer::NonEmpty(_) => {
-                std::mem::replace(self, TreePointer::Empty)
+                core::mem::replace(self, TreePointer::Empty)
             }
         }
     }
 
-    fn unwrap(self) -> Box<TreeNode<K, V>> {
+    fn unwrap(self) -> Box<TreeNode<K, V, A>, A> {
         match self {
             TreePointer::NonEmpty(val) => val,
             TreePointer::Empty => {
@@ -80,7 +204,7 @@ impl<K, V> TreePointer<K, V> {
     }
 
     fn replace(&mut self, new: Self) -> Self {
-        std::mem::replace(self, new)
+        core::mem::replace(self, new)
     }
 
     fn extract_min(&mut self) -> Option<(K, u64, V)> {
@@ -93,180 +217,227 @@ impl<K, V> TreePointer<K, V> {
                 current = &mut current.as_mut().left;
             }
 
-            let temp = current.take().unwrap();
+            let temp = Box::into_inner(current.take().unwrap());
             node = Some((temp.key, temp.hash, temp.value));
-            let _ = std::mem::replace(current, temp.right);
+            let _ = core::mem::replace(current, temp.right);
         }
         node
     }
 
-    fn remove(&mut self, hash: u64) -> Option<Self> {
-        use std::cmp::Ordering::*;
-        use TreePointer::*;
+    /// Find pointer to the node with `hash`, which key satisfies
+    /// `is_match`. If there is no such node, `Empty` pointer is returned.
+    ///
+    /// Nodes with colliding hashes are allowed on both sides of each other,
+    /// so on collision we have to look into both subtrees.
+    fn find<F>(&self, hash: u64, is_match: &mut F) -> &Self
+    where
+        F: FnMut(&K) -> bool,
+    {
+        use core::cmp::Ordering::*;
 
         let mut current = self;
 
-        let mut result = None;
-
-        // A destructuring of `current` into its components. This doesn't
-        // borrow `current` as a whole, it just borrows `node`.
-        // So we can do this: current = &mut current.as_mut().*, inside the
-        // cycle.
-        while let NonEmpty(ref mut node) = current {
-            match node.hash.cmp(&hash) {
-                Less => current = &mut current.as_mut().right,
-                Greater => current = &mut current.as_mut().left,
-                Equal => match (&node.left, &node.right) {
-                    (Empty, Empty) => {
-                        // Removing edge node, easiest case
-                        result = Some(current.replace(Empty));
-                    }
-                    (NonEmpty(_), Empty) => {
-                        // Replace current with left node, if right is `Empty`
-                        let take = node.left.take();
-                        result = Some(current.replace(take));
-                    }
-                    (Empty, NonEmpty(_)) => {
-                        // Same, but with right node
-                        let take = node.right.take();
-                        result = Some(current.replace(take));
+        while let TreePointer::NonEmpty(ref node) = current {
+            match hash.cmp(&node.hash) {
+                Less => current = &node.left,
+                Greater => current = &node.right,
+                Equal => {
+                    if is_match(&node.key) {
+                        break;
                     }
-                    (NonEmpty(_), NonEmpty(_)) => {
-                        // Complicated case
-                        //
-                        // Take our minimal node from right, write it's
-                        // data into `temp` variable, and then place on it's
-                        // place it's right node.
-                        let mut temp = node.right.extract_min().unwrap();
-                        let cur = current.as_mut();
-                        // Write our taken data into target node
-                        std::mem::swap(&mut cur.key, &mut temp.0);
-                        std::mem::swap(&mut cur.hash, &mut temp.1);
-                        std::mem::swap(&mut cur.value, &mut temp.2);
-
-                        // Return removed data
-                        result = Some(NonEmpty(Box::new(TreeNode {
-                            key: temp.0,
-                            hash: temp.1,
-                            value: temp.2,
-                            left: Empty,
-                            right: Empty,
-                        })));
+                    let left = node.left.find(hash, is_match);
+                    if left.is_non_empty() {
+                        return left;
                     }
-                },
+                    current = &node.right;
+                }
             }
         }
-        result
+        current
     }
 
-    fn iter(&self) -> TreeIter<K, V> {
-        let mut iter = TreeIter {
-            unvisited: Vec::new(),
-        };
-        iter.push_left_edge(self);
-        iter
+    /// Same as `find`, but returns mutable pointer. If there is no such node,
+    /// returned `Empty` pointer is the place, where new node with this `hash`
+    /// should be inserted.
+    fn find_mut<F>(&mut self, hash: u64, is_match: &mut F) -> &mut Self
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.find_mut_path(hash, is_match, &mut |_| {})
     }
 
-    fn debug_iter(&self) -> TreeDebugIter<K, V> {
-        let mut iter = TreeDebugIter {
-            unvisited: Vec::new(),
-        };
-        iter.push_left_edge(self);
-        iter
-    }
-}
+    /// Same as `find_mut`, but also calls `on_turn` with the side of every
+    /// turn on the way to the returned pointer.
+    fn find_mut_path<F, T>(
+        &mut self,
+        hash: u64,
+        is_match: &mut F,
+        on_turn: &mut T,
+    ) -> &mut Self
+    where
+        F: FnMut(&K) -> bool,
+        T: FnMut(Side),
+    {
+        use core::cmp::Ordering::*;
 
-impl<'a, K: 'a, V: 'a> IntoIterator for &'a TreePointer<K, V> {
-    type Item = (&'a K, &'a V);
-    type IntoIter = TreeIter<'a, K, V>;
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+        let mut current = self;
+
+        // See `remove` about destructuring `current`
+        while let TreePointer::NonEmpty(ref mut node) = current {
+            let side = match hash.cmp(&node.hash) {
+                Less => Side::Left,
+                Greater => Side::Right,
+                Equal => {
+                    if is_match(&node.key) {
+                        break;
+                    }
+                    if node.left.find(hash, is_match).is_non_empty() {
+                        Side::Left
+                    } else {
+                        Side::Right
+                    }
+                }
+            };
+            on_turn(side);
+            current = current.as_mut().child_mut(side);
+        }
+        current
     }
-}
 
-// ───── TreeIter ─────────────────────────────────────────────────────────── //
+    /// Find values of `N` distinct keys with `hashes` in one descent, which
+    /// goes down only into subtrees, that may contain unfound keys. Found
+    /// values are placed into `found` at indices of their keys.
+    fn find_many_mut<'a, F, const N: usize>(
+        &'a mut self,
+        hashes: &[u64; N],
+        is_match: &mut F,
+        found: &mut [Option<&'a mut V>; N],
+    ) where
+        F: FnMut(usize, &K) -> bool,
+    {
+        let node = match self {
+            TreePointer::Empty => return,
+            TreePointer::NonEmpty(node) => node,
+        };
+        // Split node into disjoint borrows, so each part lives for `'a`
+        let TreeNode {
+            hash,
+            key,
+            value,
+            left,
+            right,
+        } = &mut **node;
+        let mut value = Some(value);
 
-/// State of symmetrical iteration of `BinaryTree`
-pub struct TreeIter<'a, K: 'a, V: 'a> {
-    /// Stack references to `TreeNode`'s.
-    ///
-    /// Since we are using methods `push` and
-    /// `pop` of type Vec, the top of the stack is the end of the Vec.
-    ///
-    /// Node, which will be next in iteration, is placing on top of the stack,
-    /// but his ancestors, which were not visited by iteration - on the bottom.
-    /// If the stack is empty, iteration is finished.
-    unvisited: Vec<&'a TreeNode<K, V>>,
-}
+        let (mut go_left, mut go_right) = (false, false);
+        for index in 0..N {
+            if found[index].is_some() {
+                continue;
+            }
+            if hashes[index] == *hash && is_match(index, key) {
+                found[index] = value.take();
+                continue;
+            }
+            // On collision the key may be on both sides
+            go_left |= hashes[index] <= *hash;
+            go_right |= hashes[index] >= *hash;
+        }
 
-impl<'a, K: 'a, V: 'a> TreeIter<'a, K, V> {
-    fn push_left_edge(&mut self, mut tree_ptr: &'a TreePointer<K, V>) {
-        while let TreePointer::NonEmpty(ref node) = *tree_ptr {
-            self.unvisited.push(node.as_ref());
-            tree_ptr = &node.left;
+        if go_left {
+            left.find_many_mut(hashes, is_match, found);
+        }
+        if go_right {
+            right.find_many_mut(hashes, is_match, found);
         }
     }
-}
 
-impl<'a, K, V> Iterator for TreeIter<'a, K, V> {
-    type Item = (&'a K, &'a V);
-    fn next(&mut self) -> Option<Self::Item> {
-        // Find node, which will be returned by this iteration, or stop
-        // iteration.
-        let node = match self.unvisited.pop() {
-            None => return None,
-            Some(n) => n,
+    fn remove<F>(&mut self, hash: u64, is_match: &mut F) -> Option<(K, u64, V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        use TreePointer::*;
+
+        // A destructuring of `current` into its components. This doesn't
+        // borrow `current` as a whole, it just borrows `node`.
+        // So we can do this: current.replace(*), after taking node's
+        // children.
+        let current = self.find_mut(hash, is_match);
+        let node = match current {
+            Empty => return None,
+            NonEmpty(ref mut node) => node,
         };
 
-        // Next node will be the leftmost descedant of right son of this node,
-        // so place the path to him in the stack.
-        self.push_left_edge(&node.right);
+        let removed = match (&node.left, &node.right) {
+            (Empty, Empty) => {
+                // Removing edge node, easiest case
+                current.replace(Empty)
+            }
+            (NonEmpty(_), Empty) => {
+                // Replace current with left node, if right is `Empty`
+                let take = node.left.take();
+                current.replace(take)
+            }
+            (Empty, NonEmpty(_)) => {
+                // Same, but with right node
+                let take = node.right.take();
+                current.replace(take)
+            }
+            (NonEmpty(_), NonEmpty(_)) => {
+                // Complicated case
+                //
+                // Take our minimal node from right, write it's
+                // data into `temp` variable, and then place on it's
+                // place it's right node.
+                let mut temp = node.right.extract_min().unwrap();
+                let cur = current.as_mut();
+                // Write our taken data into target node
+                core::mem::swap(&mut cur.key, &mut temp.0);
+                core::mem::swap(&mut cur.hash, &mut temp.1);
+                core::mem::swap(&mut cur.value, &mut temp.2);
+
+                // Return removed data
+                return Some(temp);
+            }
+        };
+        let node = Box::into_inner(removed.unwrap());
+        Some((node.key, node.hash, node.value))
+    }
 
-        // Create the reference to the value of this node
-        Some((&node.key, &node.value))
+    /// Iterator, which stack is allocated in `alloc`.
+    fn iter_in(&self, alloc: A) -> TreeIter<'_, K, V, A> {
+        TreeIter {
+            cursor: Cursor::front(self, alloc),
+            position: ContinuationToken::new(0, 0, u64::MAX),
+        }
     }
 }
 
-// ───── TreeDebugIter ────────────────────────────────────────────────────── //
+// ───── TreeIter ─────────────────────────────────────────────────────────── //
 
 /// State of symmetrical iteration of `BinaryTree`
-struct TreeDebugIter<'a, K: 'a, V: 'a> {
-    /// Stack references to `TreeNode`'s.
-    ///
-    /// Since we are using methods `push` and
-    /// `pop` of type Vec, the top of the stack is the end of the Vec.
-    ///
-    /// Node, which will be next in iteration, is placing on top of the stack,
-    /// but his ancestors, which were not visited by iteration - on the bottom.
-    /// If the stack is empty, iteration is finished.
-    unvisited: Vec<&'a TreeNode<K, V>>,
+pub struct TreeIter<'a, K: 'a, V: 'a, A: Allocator = Global> {
+    /// Cursor, pointing to the node, which will be next in iteration. If it
+    /// is at the ghost position, iteration is finished.
+    cursor: Cursor<'a, K, V, A>,
+    /// Where to resume after the last yielded pair.
+    position: ContinuationToken,
 }
 
-impl<'a, K: 'a, V: 'a> TreeDebugIter<'a, K, V> {
-    fn push_left_edge(&mut self, mut tree_ptr: &'a TreePointer<K, V>) {
-        while let TreePointer::NonEmpty(ref node) = *tree_ptr {
-            self.unvisited.push(node.as_ref());
-            tree_ptr = &node.left;
-        }
+impl<'a, K: 'a, V: 'a, A: Allocator> TreeIter<'a, K, V, A> {
+    /// Get token to resume iteration after the last yielded pair with
+    /// `HashTree::iter_from_token`, or None if there are no more pairs.
+    pub fn next_token(&self) -> Option<ContinuationToken> {
+        self.cursor.key_value().map(|_| self.position)
     }
 }
 
-impl<'a, K, V> Iterator for TreeDebugIter<'a, K, V> {
-    type Item = &'a TreeNode<K, V>;
+impl<'a, K, V, A: Allocator> Iterator for TreeIter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
     fn next(&mut self) -> Option<Self::Item> {
-        // Find node, which will be returned by this iteration, or stop
-        // iteration.
-        let node = match self.unvisited.pop() {
-            None => return None,
-            Some(n) => n,
-        };
-
-        // Next node will be the leftmost descedant of right son of this node,
-        // so place the path to him in the stack.
-        self.push_left_edge(&node.right);
-
-        // Create the reference to the value of this node
-        Some(node)
+        let node = self.cursor.node()?;
+        self.position.advance(node.hash);
+        self.cursor.move_next();
+        Some((&node.key, &node.value))
     }
 }
 
@@ -274,9 +445,19 @@ impl<'a, K, V> Iterator for TreeDebugIter<'a, K, V> {
 
 /// `HashTree` is a collection of pairs which are sorted by hash,
 /// generated for every key.
-pub struct HashTree<K, V> {
-    root: TreePointer<K, V>,
-    state: ahash::RandomState,
+///
+/// Keys are hashed with `ahash` by default, but any `BuildHasher` can be
+/// supplied with `HashTree::with_hasher`. Keys with colliding hashes are
+/// stored side by side and told apart by `Eq`.
+///
+/// Nodes and iterator stacks are allocated with `A`, which is the global
+/// allocator by default. Any `allocator_api2::alloc::Allocator` can be
+/// supplied with `HashTree::new_in` or `HashTree::with_hasher_in`, for
+/// example an arena, to free all nodes at once.
+pub struct HashTree<K, V, S = ahash::RandomState, A: Allocator = Global> {
+    root: TreePointer<K, V, A>,
+    state: S,
+    alloc: A,
 }
 
 impl<K, V> HashTree<K, V>
@@ -284,67 +465,182 @@ where
     K: core::hash::Hash + Eq,
 {
     /// Create new empty `HashTree`.
+    ///
+    /// Without `std` feature there is no OS randomness, so seeds are not
+    /// random. Use `new_with_seeds` with seeds from a hardware RNG instead.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let state = ahash::RandomState::new();
-        HashTree {
-            root: TreePointer::Empty,
-            state,
-        }
+        Self::with_hasher(ahash::RandomState::new())
     }
 
     /// Create new empty `HashTree` with custom seed. It will always hash same
     /// keys with the same hashes, so the order of elements in binary tree will
     /// be preserved. May be useful for serialization.
     pub fn new_with_seed(seed: u64) -> Self {
-        let state = ahash::RandomState::with_seeds(seed, seed, seed, seed);
+        Self::with_hasher(ahash::RandomState::with_seeds(
+            seed, seed, seed, seed,
+        ))
+    }
+
+    /// Same as `new_with_seed`, but all 256 bits of `ahash` state are set.
+    /// Useful in `no_std` environments, where seeds come from a hardware
+    /// RNG.
+    pub fn new_with_seeds(k0: u64, k1: u64, k2: u64, k3: u64) -> Self {
+        Self::with_hasher(ahash::RandomState::with_seeds(k0, k1, k2, k3))
+    }
+}
+
+impl<K, V, S> HashTree<K, V, S>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+{
+    /// Create new empty `HashTree`, which will use `state` to hash keys.
+    pub fn with_hasher(state: S) -> Self {
+        Self::with_hasher_in(state, Global)
+    }
+}
+
+impl<K, V, A> HashTree<K, V, ahash::RandomState, A>
+where
+    K: core::hash::Hash + Eq,
+    A: Allocator + Clone,
+{
+    /// Create new empty `HashTree`, which will allocate nodes in `alloc`.
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_hasher_in(ahash::RandomState::new(), alloc)
+    }
+}
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Create new empty `HashTree`, which will use `state` to hash keys and
+    /// allocate nodes in `alloc`.
+    pub fn with_hasher_in(state: S, alloc: A) -> Self {
         HashTree {
             root: TreePointer::Empty,
             state,
+            alloc,
         }
     }
 
+    /// Get a reference to the `BuildHasher` of this `HashTree`.
+    pub fn hasher(&self) -> &S {
+        &self.state
+    }
+
+    /// Get a reference to the allocator of this `HashTree`.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
     /// Insert an element to a `HashTree`. If a value is already present in the
     /// `HashTree`, the old value is returned, otherwise None is returned.
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
         // Generate hash for key
-        let hash = self.state.hash_one(std::borrow::Borrow::borrow(&key));
-
-        // If BinaryTree is empty, create root node
-        let mut parent = &mut self.root;
-        loop {
-            match parent {
-                TreePointer::Empty => {
-                    *parent = TreePointer::new(
-                        TreePointer::Empty,
-                        key,
-                        hash,
-                        value,
-                        TreePointer::Empty,
-                    );
-                    return None;
-                }
-                TreePointer::NonEmpty(node) => match hash.cmp(&node.hash) {
-                    std::cmp::Ordering::Less => {
-                        parent = &mut node.left;
-                    }
-                    std::cmp::Ordering::Equal => {
-                        let temp = std::mem::replace(&mut node.value, value);
-                        return Some(temp);
-                    }
-                    std::cmp::Ordering::Greater => {
-                        parent = &mut node.right;
-                    }
-                },
+        let hash = self.state.hash_one(&key);
+        self.insert_hashed(hash, key, value)
+    }
+
+    /// Same as `insert`, but `hash` of `key` is already computed.
+    fn insert_hashed(&mut self, hash: u64, key: K, value: V) -> Option<V> {
+        // If key is not present, `find_mut` stops on the `Empty` pointer,
+        // where new node should be placed. If BinaryTree is empty, it is root.
+        let pointer = self.root.find_mut(hash, &mut |k| *k == key);
+        let old_value = match pointer {
+            TreePointer::Empty => {
+                *pointer = TreePointer::new(
+                    TreePointer::Empty,
+                    key,
+                    hash,
+                    value,
+                    TreePointer::Empty,
+                    self.alloc.clone(),
+                );
+                None
+            }
+            TreePointer::NonEmpty(node) => {
+                Some(core::mem::replace(&mut node.value, value))
             }
+        };
+        self.check_invariants();
+        old_value
+    }
+
+    /// Insert a pair, only if `key` is not present yet. Returns a reference
+    /// to the inserted value, or gives the pair back in `OccupiedError`
+    /// without touching the old value.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<&mut V, OccupiedError<K, V>> {
+        let hash = self.state.hash_one(&key);
+
+        let pointer = self.root.find_mut(hash, &mut |k| *k == key);
+        if pointer.is_non_empty() {
+            return Err(OccupiedError { key, value });
         }
+        *pointer = TreePointer::new(
+            TreePointer::Empty,
+            key,
+            hash,
+            value,
+            TreePointer::Empty,
+            self.alloc.clone(),
+        );
+
+        // Tree can't be checked while `pointer` is borrowed, so find the
+        // new node again by address of its key
+        #[cfg(all(debug_assertions, feature = "debug-invariants"))]
+        let pointer = {
+            let key: *const K = &pointer.as_ref().key;
+            self.check_invariants();
+            self.root.find_mut(hash, &mut |k| core::ptr::eq(k, key))
+        };
+        Ok(&mut pointer.as_mut().value)
+    }
+
+    /// Same as `insert`, but returns an error instead of aborting, if a new
+    /// node can't be allocated. Then the pair is dropped, and the tree is
+    /// left unchanged.
+    pub fn try_insert_alloc(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, TryReserveError> {
+        let hash = self.state.hash_one(&key);
+
+        let pointer = self.root.find_mut(hash, &mut |k| *k == key);
+        let old_value = match pointer {
+            TreePointer::Empty => {
+                *pointer = TreePointer::try_new(
+                    TreePointer::Empty,
+                    key,
+                    hash,
+                    value,
+                    TreePointer::Empty,
+                    self.alloc.clone(),
+                )?;
+                None
+            }
+            TreePointer::NonEmpty(node) => {
+                Some(core::mem::replace(&mut node.value, value))
+            }
+        };
+        self.check_invariants();
+        Ok(old_value)
     }
 
     /// Get value by key. Returns an Optional value. If there is no value by
     /// this key - None is returned.
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
-        K: std::borrow::Borrow<Q>,
-        Q: std::hash::Hash + Eq,
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
     {
         match self.find_pointer(key) {
             TreePointer::Empty => None,
@@ -352,93 +648,148 @@ where
         }
     }
 
-    /// Remove pair from `HashTree`, returns value, or None if not present.
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    /// Get mutable reference to the value by key, or None if not present.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
-        K: std::borrow::Borrow<Q>,
-        Q: std::hash::Hash + Eq,
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
     {
-        match self.root.remove(self.state.hash_one(key)) {
-            Some(inner) => match inner {
-                TreePointer::Empty => None,
-                TreePointer::NonEmpty(node) => Some(node.value),
-            },
-            None => None,
+        match self.find_pointer_mut(key) {
+            TreePointer::Empty => None,
+            TreePointer::NonEmpty(node) => Some(&mut node.value),
+        }
+    }
+
+    /// Get stored key and its value, or None if not present.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        match self.find_pointer(key) {
+            TreePointer::Empty => None,
+            TreePointer::NonEmpty(node) => Some((&node.key, &node.value)),
+        }
+    }
+
+    /// Check if `HashTree` contains a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.find_pointer(key).is_non_empty()
+    }
+
+    /// Get mutable references to values of `N` keys at once. Returns None,
+    /// if any key is not present or two keys are equal.
+    ///
+    /// ```
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new();
+    /// tree.insert("a", 1);
+    /// tree.insert("b", 2);
+    ///
+    /// if let Some([a, b]) = tree.get_many_mut(["a", "b"]) {
+    ///     core::mem::swap(a, b);
+    /// }
+    /// assert_eq!(tree["a"], 2);
+    /// assert_eq!(tree.get_many_mut(["a", "a"]), None);
+    /// ```
+    pub fn get_many_mut<Q, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> Option<[&mut V; N]>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        // Each node is given to one key only, so if two keys are equivalent,
+        // the second one stays unfound, and None is returned
+        let hashes = keys.map(|key| self.state.hash_one(key));
+        let mut found = core::array::from_fn(|_| None);
+        self.root.find_many_mut(
+            &hashes,
+            &mut |index, k| keys[index].equivalent(k),
+            &mut found,
+        );
+
+        if found.iter().any(Option::is_none) {
+            return None;
         }
+        Some(found.map(Option::unwrap))
+    }
+
+    /// Remove pair from `HashTree`, returns value, or None if not present.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Remove pair from `HashTree`, returns stored key and value, or None if
+    /// not present.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        let removed = self
+            .root
+            .remove(hash, &mut |k| key.equivalent(k))
+            .map(|(key, _, value)| (key, value));
+        self.check_invariants();
+        removed
     }
 
     /// Get iterator for `HashTree`
-    pub fn iter<'a>(&'a mut self) -> TreeIter<'a, K, V> {
-        self.root.iter()
+    pub fn iter<'a>(&'a mut self) -> TreeIter<'a, K, V, A> {
+        self.root.iter_in(self.alloc.clone())
     }
 
-    fn find_pointer<Q: ?Sized>(&self, key: &Q) -> &TreePointer<K, V>
+    fn find_pointer<Q>(&self, key: &Q) -> &TreePointer<K, V, A>
     where
-        K: std::borrow::Borrow<Q>,
-        Q: std::hash::Hash + Eq,
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
     {
         let hash = self.state.hash_one(key);
-        let mut parent = &self.root;
-
-        loop {
-            match parent {
-                TreePointer::NonEmpty(node) => match hash.cmp(&node.hash) {
-                    std::cmp::Ordering::Less => {
-                        parent = &node.left;
-                        continue;
-                    }
-                    std::cmp::Ordering::Equal => return parent,
-                    std::cmp::Ordering::Greater => {
-                        parent = &node.right;
-                        continue;
-                    }
-                },
-                TreePointer::Empty => return parent,
-            }
-        }
+        self.root.find(hash, &mut |k| key.equivalent(k))
+    }
+
+    fn find_pointer_mut<Q>(&mut self, key: &Q) -> &mut TreePointer<K, V, A>
+    where
+        Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = self.state.hash_one(key);
+        self.root.find_mut(hash, &mut |k| key.equivalent(k))
     }
 }
 
-impl<'a, K: 'a, V: 'a> IntoIterator for &'a HashTree<K, V> {
+impl<'a, K: 'a, V: 'a, S, A> IntoIterator for &'a HashTree<K, V, S, A>
+where
+    A: Allocator + Clone,
+{
     type Item = (&'a K, &'a V);
-    type IntoIter = TreeIter<'a, K, V>;
+    type IntoIter = TreeIter<'a, K, V, A>;
     fn into_iter(self) -> Self::IntoIter {
-        self.root.iter()
+        self.root.iter_in(self.alloc.clone())
     }
 }
 
-impl<K, V> std::fmt::Debug for HashTree<K, V>
+impl<K, V, S, A> core::fmt::Debug for HashTree<K, V, S, A>
 where
-    K: std::fmt::Display,
-    V: std::fmt::Display,
+    K: core::fmt::Debug,
+    V: core::fmt::Debug,
+    A: Allocator + Clone,
 {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut result = Err(std::fmt::Error);
-        for item in self.root.debug_iter() {
-            let left = match &item.left {
-                TreePointer::Empty => format!("Empty"),
-                TreePointer::NonEmpty(node) => format!("key is {}", node.key),
-            };
-            let right = match &item.right {
-                TreePointer::Empty => format!("Empty"),
-                TreePointer::NonEmpty(node) => format!("key is {}", node.key),
-            };
-            result = f.write_str(&format!(
-                "\n\nHash: {}\nKey: {}\nValue: {}\nLeft: {}\nRight: {}",
-                item.hash, item.key, item.value, left, right
-            ));
-        }
-        match result {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
-        }
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map().entries(self).finish()
     }
 }
 
-impl<K, V, Q> std::ops::Index<&Q> for HashTree<K, V>
+impl<K, V, S, A, Q> core::ops::Index<&Q> for HashTree<K, V, S, A>
 where
-    K: std::hash::Hash + Eq + std::borrow::Borrow<Q>,
-    Q: Eq + std::hash::Hash + ?Sized,
+    K: core::hash::Hash + Eq,
+    Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
 {
     type Output = V;
 
@@ -450,6 +801,128 @@ where
     }
 }
 
+impl<K, V, S, A, Q> core::ops::IndexMut<&Q> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    Q: core::hash::Hash + Equivalent<K> + ?Sized,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    fn index_mut(&mut self, index: &Q) -> &mut Self::Output {
+        match self.find_pointer_mut(index) {
+            TreePointer::Empty => panic!("No entry found for key"),
+            TreePointer::NonEmpty(node) => &mut node.value,
+        }
+    }
+}
+
+/// Copies the tree node by node, so the clone has the same shape and hasher.
+/// Done without recursion, so deep trees can't overflow the stack.
+impl<K, V, S, A> Clone for HashTree<K, V, S, A>
+where
+    K: Clone,
+    V: Clone,
+    S: Clone,
+    A: Allocator + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut root = TreePointer::Empty;
+        // Pairs of a source subtree and a place for its copy
+        let mut unvisited = AllocVec::new_in(self.alloc.clone());
+        unvisited.push((&self.root, &mut root));
+
+        while let Some((source, target)) = unvisited.pop() {
+            if let TreePointer::NonEmpty(node) = source {
+                *target = TreePointer::new(
+                    TreePointer::Empty,
+                    node.key.clone(),
+                    node.hash,
+                    node.value.clone(),
+                    TreePointer::Empty,
+                    self.alloc.clone(),
+                );
+                if let TreePointer::NonEmpty(copy) = target {
+                    let TreeNode { left, right, .. } = &mut **copy;
+                    unvisited.push((&node.left, left));
+                    unvisited.push((&node.right, right));
+                }
+            }
+        }
+        drop(unvisited);
+
+        HashTree {
+            root,
+            state: self.state.clone(),
+            alloc: self.alloc.clone(),
+        }
+    }
+}
+
+/// Trees are equal, if they contain equal pairs, no matter of their shapes
+/// and hashers.
+impl<K, V, S, A> PartialEq for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    V: PartialEq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.into_iter().count() == other.into_iter().count()
+            && self
+                .into_iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K, V, S, A> Eq for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    V: Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+}
+
+/// Equal trees may store pairs in different orders, if their hashers
+/// differ, so pairs are hashed one by one with a fixed hasher, and only the
+/// order independent sum of their hashes is written to `state`.
+impl<K, V, S, A> core::hash::Hash for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash,
+    V: core::hash::Hash,
+    A: Allocator + Clone,
+{
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        use core::hash::Hasher;
+
+        let mut len = 0;
+        let mut sum = 0u64;
+        for pair in self {
+            let mut hasher = ahash::AHasher::default();
+            core::hash::Hash::hash(&pair, &mut hasher);
+            sum = sum.wrapping_add(hasher.finish());
+            len += 1;
+        }
+        state.write_usize(len);
+        state.write_u64(sum);
+    }
+}
+
+impl<K, V, S, A> Default for HashTree<K, V, S, A>
+where
+    S: Default,
+    A: Allocator + Default,
+{
+    fn default() -> Self {
+        HashTree {
+            root: TreePointer::Empty,
+            state: S::default(),
+            alloc: A::default(),
+        }
+    }
+}
+
 // ───── Tests ────────────────────────────────────────────────────────────── //
 
 #[cfg(test)]
@@ -465,7 +938,8 @@ mod tests {
         let pointer = generate_unhashed_tree();
 
         // Iterate it
-        let v: Vec<_> = pointer.iter().map(|(&s1, &s2)| (s1, s2)).collect();
+        let v: Vec<_> =
+            pointer.iter_in(Global).map(|(&s1, &s2)| (s1, s2)).collect();
 
         assert_eq!(
             v,
@@ -587,6 +1061,169 @@ mod tests {
         assert_eq!(tree["mecha"], "mechaV");
     }
 
+    #[test]
+    fn test_try_insert() {
+        let mut tree = create_tree();
+
+        let value = tree.try_insert("ios", "iosV").unwrap();
+        assert_eq!(*value, "iosV");
+        *value = "iosV2";
+        assert_eq!(tree["ios"], "iosV2");
+
+        assert_eq!(
+            tree.try_insert("mecha", "newV"),
+            Err(OccupiedError {
+                key: "mecha",
+                value: "newV"
+            })
+        );
+        assert_eq!(tree["mecha"], "mechaV");
+    }
+
+    #[test]
+    fn test_try_insert_alloc() {
+        let mut tree = create_tree();
+
+        assert_eq!(tree.try_insert_alloc("ios", "iosV"), Ok(None));
+        assert_eq!(tree.try_insert_alloc("ios", "iosV2"), Ok(Some("iosV")));
+        assert_eq!(tree["ios"], "iosV2");
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut tree = create_tree();
+
+        *tree.get_mut("mecha").unwrap() = "newV";
+        assert_eq!(tree.get("mecha"), Some(&"newV"));
+        assert_eq!(tree.get_mut("Nothing"), None);
+
+        tree["robot"] = "robotV2";
+        assert_eq!(tree["robot"], "robotV2");
+    }
+
+    #[test]
+    fn test_get_key_value() {
+        let tree = create_tree();
+
+        assert_eq!(tree.get_key_value("droid"), Some((&"droid", &"droidV")));
+        assert_eq!(tree.get_key_value("Nothing"), None);
+        assert!(tree.contains_key("droid"));
+        assert!(!tree.contains_key("Nothing"));
+    }
+
+    #[test]
+    fn test_equivalent() {
+        /// Borrowed form of `(String, u32)`, hashed the same way.
+        #[derive(Hash)]
+        struct Borrowed<'a>(&'a str, u32);
+
+        impl Equivalent<(String, u32)> for Borrowed<'_> {
+            fn equivalent(&self, key: &(String, u32)) -> bool {
+                self.0 == key.0 && self.1 == key.1
+            }
+        }
+
+        let mut tree = HashTree::new();
+        tree.insert(("a".to_string(), 1), 10);
+        tree.insert(("b".to_string(), 2), 20);
+
+        assert_eq!(tree.get(&Borrowed("a", 1)), Some(&10));
+        assert_eq!(tree[&Borrowed("b", 2)], 20);
+        assert!(!tree.contains_key(&Borrowed("a", 2)));
+        tree[&Borrowed("a", 1)] += 1;
+        assert_eq!(tree.remove(&Borrowed("a", 1)), Some(11));
+        assert_eq!(tree.get(&("b".to_string(), 2)), Some(&20));
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        let mut tree = create_tree();
+
+        assert_eq!(tree.remove_entry("Jaeger"), Some(("Jaeger", "JaegerV")));
+        assert_eq!(tree.remove_entry("Jaeger"), None);
+        assert!(!tree.contains_key("Jaeger"));
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut tree = create_tree();
+
+        let [a, b, c] = tree.get_many_mut(["mecha", "robot", "droid"]).unwrap();
+        core::mem::swap(a, b);
+        *c = "droidV2";
+        assert_eq!(tree["mecha"], "robotV");
+        assert_eq!(tree["robot"], "mechaV");
+        assert_eq!(tree["droid"], "droidV2");
+
+        assert_eq!(tree.get_many_mut(["mecha", "Nothing"]), None);
+        assert_eq!(tree.get_many_mut(["mecha", "Jaeger", "mecha"]), None);
+        assert_eq!(tree.get_many_mut::<str, 0>([]), Some([]));
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut tree = create_tree();
+        let mut clone = tree.clone();
+
+        assert_eq!(clone.stats(), tree.stats());
+        assert!(tree.iter().eq(clone.iter()));
+        assert_eq!(clone.validate(), Ok(()));
+
+        clone.insert("ios", "iosV");
+        assert!(!tree.contains_key("ios"));
+    }
+
+    #[test]
+    fn test_eq_and_hash() {
+        fn hash_of<T: core::hash::Hash>(value: &T) -> u64 {
+            RandomState::with_seeds(1, 2, 3, 4).hash_one(value)
+        }
+
+        let mut tree = HashTree::new_with_seed(1);
+        let mut other = HashTree::new_with_seed(2);
+        for key in 0..100 {
+            tree.insert(key, key * 2);
+            other.insert(99 - key, (99 - key) * 2);
+        }
+        assert!(tree.stats() != other.stats());
+        assert_eq!(tree, other);
+        assert_eq!(hash_of(&tree), hash_of(&other));
+
+        other.insert(0, 1);
+        assert_ne!(tree, other);
+        other.insert(0, 0);
+        other.insert(100, 200);
+        assert_ne!(tree, other);
+        assert_ne!(hash_of(&tree), hash_of(&other));
+    }
+
+    #[test]
+    fn test_default() {
+        #[derive(Default)]
+        struct Wrapper {
+            tree: HashTree<
+                u32,
+                u32,
+                core::hash::BuildHasherDefault<ahash::AHasher>,
+            >,
+        }
+
+        let mut wrapper = Wrapper::default();
+        assert_eq!(wrapper.tree.get(&1), None);
+        wrapper.tree.insert(1, 1);
+        assert_eq!(wrapper.tree[&1], 1);
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut tree = HashTree::new_with_seed(1);
+        assert_eq!(format!("{:?}", tree), "{}");
+
+        tree.insert(1, 10.);
+        tree.insert(2, 20.);
+        assert_eq!(format!("{:?}", tree), "{2: 20.0, 1: 10.0}");
+    }
+
     #[test]
     fn test_removing_node_order() {
         let mut tree = HashTree::new_with_seed(1);
@@ -707,6 +1344,7 @@ mod tests {
             state.hash_one("mecha"),
             "mechaV",
             Empty,
+            Global,
         );
         let subtree_rlrl = TreePointer::new(
             Empty,
@@ -714,6 +1352,7 @@ mod tests {
             state.hash_one("GingerBread"),
             "GingerBreadV",
             Empty,
+            Global,
         );
         let subtree_rlr = TreePointer::new(
             subtree_rlrl,
@@ -721,6 +1360,7 @@ mod tests {
             state.hash_one("Android"),
             "AndroidV",
             Empty,
+            Global,
         );
         let subtree_rl = TreePointer::new(
             Empty,
@@ -728,6 +1368,7 @@ mod tests {
             state.hash_one("droid"),
             "droidV",
             subtree_rlr,
+            Global,
         );
         let subtree_r = TreePointer::new(
             subtree_rl,
@@ -735,14 +1376,15 @@ mod tests {
             state.hash_one("robot"),
             "robotV",
             Empty,
+            Global,
         );
-        let tree = TreePointer::new(
+        TreePointer::new(
             subtree_l,
             "Jaeger",
             state.hash_one("Jaeger"),
             "JaegerV",
             subtree_r,
-        );
-        tree
+            Global,
+        )
     }
 }
@@ -0,0 +1,240 @@
+//! Model-based tests: random sequences of operations are applied both to a
+//! `HashTree` and to a `std::collections::HashMap`, and results must agree.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use super::{HashTree, IndexedHashTree, InvariantError};
+
+// ───── Colliding hasher ─────────────────────────────────────────────────── //
+
+/// `BuildHasher`, which keeps only `mask` bits of `ahash` output, so a lot of
+/// different keys get equal hashes. Other modules test collisions with it
+/// next to their own tests.
+#[derive(Clone)]
+pub(super) struct CollidingState {
+    state: ahash::RandomState,
+    mask: u64,
+}
+
+impl CollidingState {
+    pub(super) fn new(seed: u64, mask: u64) -> Self {
+        CollidingState {
+            state: ahash::RandomState::with_seeds(seed, seed, seed, seed),
+            mask,
+        }
+    }
+}
+
+pub(super) struct CollidingHasher {
+    hasher: ahash::AHasher,
+    mask: u64,
+}
+
+impl Hasher for CollidingHasher {
+    fn finish(&self) -> u64 {
+        self.hasher.finish() & self.mask
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes)
+    }
+}
+
+impl BuildHasher for CollidingState {
+    type Hasher = CollidingHasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        CollidingHasher {
+            hasher: self.state.build_hasher(),
+            mask: self.mask,
+        }
+    }
+}
+
+// ───── Model ────────────────────────────────────────────────────────────── //
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(u16, u32),
+    Remove(u16),
+    Get(u16),
+    Iter,
+    BulkLoad(Vec<(u16, u32)>),
+    Rebalance,
+}
+
+fn op_strategy(max_key: u16) -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (0..max_key, any::<u32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+        2 => (0..max_key).prop_map(Op::Remove),
+        2 => (0..max_key).prop_map(Op::Get),
+        1 => Just(Op::Iter),
+        1 => vec((0..max_key, any::<u32>()), 0..32).prop_map(Op::BulkLoad),
+        1 => Just(Op::Rebalance),
+    ]
+}
+
+fn run_model<S: BuildHasher>(
+    mut tree: HashTree<u16, u32, S>,
+    ops: Vec<Op>,
+) -> Result<(), TestCaseError> {
+    let mut model = HashMap::new();
+
+    for op in ops {
+        match op {
+            Op::Insert(key, value) => {
+                prop_assert_eq!(
+                    tree.insert(key, value),
+                    model.insert(key, value)
+                )
+            }
+            Op::Remove(key) => {
+                prop_assert_eq!(tree.remove(&key), model.remove(&key))
+            }
+            Op::Get(key) => prop_assert_eq!(tree.get(&key), model.get(&key)),
+            Op::Iter => {
+                let mut pairs: Vec<_> =
+                    tree.iter().map(|(&k, &v)| (k, v)).collect();
+                let mut expected: Vec<_> =
+                    model.iter().map(|(&k, &v)| (k, v)).collect();
+                pairs.sort_unstable();
+                expected.sort_unstable();
+                prop_assert_eq!(pairs, expected);
+            }
+            Op::BulkLoad(pairs) => {
+                tree.bulk_load(pairs.iter().copied());
+                model.extend(pairs);
+            }
+            Op::Rebalance => tree.rebalance(),
+        }
+        // Full validation after every operation is slow, so it's done only
+        // with `debug-invariants`
+        if cfg!(feature = "debug-invariants") {
+            prop_assert_eq!(tree.validate(), Ok::<(), InvariantError>(()));
+        }
+    }
+
+    prop_assert_eq!(tree.validate(), Ok::<(), InvariantError>(()));
+    prop_assert_eq!(tree.iter().count(), model.len());
+    for (key, value) in &model {
+        prop_assert_eq!(tree.get(key), Some(value));
+    }
+    Ok(())
+}
+
+fn run_indexed_model<S: BuildHasher>(
+    mut tree: IndexedHashTree<u16, u32, S>,
+    ops: Vec<Op>,
+) -> Result<(), TestCaseError> {
+    let mut model = HashMap::new();
+
+    for op in ops {
+        match op {
+            Op::Insert(key, value) => {
+                prop_assert_eq!(
+                    tree.insert(key, value),
+                    model.insert(key, value)
+                )
+            }
+            Op::Remove(key) => {
+                prop_assert_eq!(tree.remove(&key), model.remove(&key))
+            }
+            Op::Get(key) => prop_assert_eq!(tree.get(&key), model.get(&key)),
+            Op::Iter | Op::Rebalance => {
+                // Pairs come in hash order
+                let hashes: Vec<u64> = tree
+                    .iter()
+                    .map(|(key, _)| tree.hasher().hash_one(key))
+                    .collect();
+                prop_assert!(hashes.windows(2).all(|w| w[0] <= w[1]));
+            }
+            Op::BulkLoad(pairs) => {
+                for (key, value) in pairs {
+                    tree.insert(key, value);
+                    model.insert(key, value);
+                }
+            }
+        }
+        prop_assert_eq!(tree.len(), model.len());
+    }
+
+    prop_assert_eq!(tree.iter().count(), model.len());
+    for (key, value) in &model {
+        prop_assert_eq!(tree.get(key), Some(value));
+    }
+    Ok(())
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+proptest! {
+    // Every case runs up to 512 operations
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_model(
+        seed in any::<u64>(),
+        ops in vec(op_strategy(256), 0..512),
+    ) {
+        run_model(HashTree::new_with_seed(seed), ops)?;
+    }
+
+    #[test]
+    fn test_model_with_collisions(
+        seed in any::<u64>(),
+        ops in vec(op_strategy(64), 0..512),
+    ) {
+        // Only 8 different hashes for 64 keys
+        let state = CollidingState::new(seed, 0b111);
+        run_model(HashTree::with_hasher(state), ops)?;
+    }
+
+    #[test]
+    fn test_indexed_model_with_collisions(
+        seed in any::<u64>(),
+        ops in vec(op_strategy(64), 0..512),
+    ) {
+        let state = CollidingState::new(seed, 0b111);
+        run_indexed_model(IndexedHashTree::with_hasher(state), ops)?;
+    }
+}
+
+#[test]
+fn test_all_hashes_collide() {
+    let mut tree = HashTree::with_hasher(CollidingState::new(1, 0));
+    for key in 0..32u16 {
+        assert_eq!(tree.insert(key, u32::from(key)), None);
+    }
+    assert_eq!(tree.stats().colliding_hashes, 31);
+
+    for key in (0..32).step_by(3) {
+        assert_eq!(tree.remove(&key), Some(u32::from(key)));
+        assert_eq!(tree.validate(), Ok(()));
+    }
+    for key in 0..32 {
+        let expected = (key % 3 != 0).then_some(u32::from(key));
+        assert_eq!(tree.get(&key).copied(), expected);
+    }
+}
+
+#[test]
+fn test_get_many_mut_with_collisions() {
+    let mut tree = HashTree::with_hasher(CollidingState::new(1, 0b1));
+    for key in 0..32u16 {
+        tree.insert(key, u32::from(key));
+    }
+
+    let keys = [&0, &31, &8, &17, &2];
+    for value in tree.get_many_mut(keys).unwrap() {
+        *value += 100;
+    }
+    for key in 0..32 {
+        let bonus = if keys.contains(&&key) { 100 } else { 0 };
+        assert_eq!(tree.get(&key), Some(&(u32::from(key) + bonus)));
+    }
+    assert_eq!(tree.get_many_mut([&1, &32]), None);
+}
@@ -0,0 +1,305 @@
+//! Pagination with continuation tokens. A token stores the last yielded
+//! hash, how many pairs with this hash were yielded and the end of the
+//! iterated range, not a pointer into the tree, so it stays valid after any
+//! mutation. A page resumes with one descent to the hash, like `range_hash`.
+
+use core::str::FromStr;
+
+use allocator_api2::alloc::Allocator;
+
+use super::{HashTree, InvalidTokenError, TreeRange};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get iterator over pairs in hash order, which resumes after the pair,
+    /// where `token` was taken with `TreeRange::next_token` or
+    /// `TreeIter::next_token`, and stops at the end of the same range. Pairs,
+    /// inserted or removed since then, are seen or skipped by their hashes,
+    /// except for pairs with colliding hashes (see `ContinuationToken`).
+    ///
+    /// ```
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new_with_seed(1);
+    /// for key in 0..10 {
+    ///     tree.insert(key, key);
+    /// }
+    /// let mut pages = Vec::new();
+    /// let mut iter = tree.range_hash(..);
+    /// loop {
+    ///     pages.push(iter.by_ref().take(3).count());
+    ///     let token = match iter.next_token() {
+    ///         Some(token) => token.to_string(),
+    ///         None => break,
+    ///     };
+    ///     iter = tree.iter_from_token(&token.parse().unwrap());
+    /// }
+    /// assert_eq!(pages, [3, 3, 3, 1]);
+    /// ```
+    pub fn iter_from_token(
+        &self,
+        token: &ContinuationToken,
+    ) -> TreeRange<'_, K, V, A> {
+        let mut iter = self.range_hash(token.hash..=token.end);
+        iter.skip_colliding(token.hash, token.skip);
+        iter
+    }
+}
+
+// ───── ContinuationToken ────────────────────────────────────────────────── //
+
+/// Position in hash order, between two pairs, in a range of hashes.
+/// Serialized to 24 bytes, or to 48 hex digits with `Display` and
+/// `FromStr`.
+///
+/// Pairs with the hash of the last yielded pair are told apart only by
+/// their number. If some of them, which were yielded, are removed before
+/// the iteration resumes, as many pairs with this hash, which were not
+/// yielded, are skipped. Keys of a good hasher almost never collide, so
+/// only groups of colliding keys are affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContinuationToken {
+    /// Hash of the last yielded pair.
+    hash: u64,
+    /// Number of yielded pairs with `hash`. More than one only on collision.
+    skip: u64,
+    /// The last hash of the range, `u64::MAX` for unbounded ones.
+    end: u64,
+}
+
+impl ContinuationToken {
+    pub(super) fn new(hash: u64, skip: u64, end: u64) -> Self {
+        ContinuationToken { hash, skip, end }
+    }
+
+    /// Move the position after a pair with `hash`.
+    pub(super) fn advance(&mut self, hash: u64) {
+        if hash == self.hash {
+            self.skip += 1;
+        } else {
+            *self = ContinuationToken::new(hash, 1, self.end);
+        }
+    }
+
+    /// Serialize the token into bytes.
+    pub fn to_bytes(&self) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        let fields = [self.hash, self.skip, self.end];
+        for (chunk, field) in bytes.chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize the token from bytes, made by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidTokenError> {
+        if bytes.len() != 24 {
+            return Err(InvalidTokenError);
+        }
+        let mut fields = bytes.chunks_exact(8).map(|chunk| {
+            let mut field = [0; 8];
+            field.copy_from_slice(chunk);
+            u64::from_be_bytes(field)
+        });
+        let mut next = || fields.next().ok_or(InvalidTokenError);
+        Ok(ContinuationToken::new(next()?, next()?, next()?))
+    }
+}
+
+impl core::fmt::Display for ContinuationToken {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:016x}{:016x}{:016x}", self.hash, self.skip, self.end)
+    }
+}
+
+impl FromStr for ContinuationToken {
+    type Err = InvalidTokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // `from_str_radix` would also accept a sign
+        if s.len() != 48 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(InvalidTokenError);
+        }
+        let parse = |digits| {
+            u64::from_str_radix(digits, 16).map_err(|_| InvalidTokenError)
+        };
+        Ok(ContinuationToken::new(
+            parse(&s[..16])?,
+            parse(&s[16..32])?,
+            parse(&s[32..])?,
+        ))
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use core::hash::BuildHasher;
+
+    use super::*;
+    use crate::hash_tree::model_tests::CollidingState;
+
+    /// Collect all pairs by pages of `page_len`, calling `between` after
+    /// every page.
+    fn paginate<F>(
+        tree: &mut HashTree<u32, u32>,
+        page_len: usize,
+        mut between: F,
+    ) -> Vec<u32>
+    where
+        F: FnMut(&mut HashTree<u32, u32>),
+    {
+        let mut keys = Vec::new();
+        let mut token = None;
+        loop {
+            token = match token {
+                None => {
+                    let mut iter = tree.iter();
+                    keys.extend(iter.by_ref().take(page_len).map(|(&k, _)| k));
+                    iter.next_token()
+                }
+                Some(ref token) => {
+                    let mut iter = tree.iter_from_token(token);
+                    keys.extend(iter.by_ref().take(page_len).map(|(&k, _)| k));
+                    iter.next_token()
+                }
+            };
+            if token.is_none() {
+                return keys;
+            }
+            between(tree);
+        }
+    }
+
+    fn create_tree(len: u32) -> HashTree<u32, u32> {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..len {
+            tree.insert(key, key);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_pages() {
+        for (len, page_len) in [(0, 10), (1, 1), (100, 7), (100, 100)] {
+            let mut tree = create_tree(len);
+            let expected: Vec<u32> = tree.iter().map(|(&k, _)| k).collect();
+            assert_eq!(paginate(&mut tree, page_len, |_| {}), expected);
+        }
+    }
+
+    #[test]
+    fn test_pages_with_mutations() {
+        let mut tree = create_tree(100);
+        let mut next_key = 100;
+        let keys = paginate(&mut tree, 10, |tree| {
+            tree.insert(next_key, 0);
+            tree.remove(&(next_key - 100));
+            next_key += 1;
+        });
+
+        // No pair is yielded twice, and pairs, which lived all the time,
+        // are yielded
+        let mut unique = keys.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        assert_eq!(unique.len(), keys.len());
+        for key in next_key - 100..100 {
+            assert!(keys.contains(&key));
+        }
+    }
+
+    #[test]
+    fn test_partition_pages() {
+        let tree = create_tree(1000);
+        for part in 0..4 {
+            let expected: Vec<u32> =
+                tree.iter_prefix(part, 2).map(|(&k, _)| k).collect();
+            let mut keys = Vec::new();
+            let mut iter = tree.iter_prefix(part, 2);
+            loop {
+                keys.extend(iter.by_ref().take(10).map(|(&k, _)| k));
+                match iter.next_token() {
+                    Some(token) => iter = tree.iter_from_token(&token),
+                    None => break,
+                }
+            }
+            assert_eq!(keys, expected);
+        }
+    }
+
+    #[test]
+    fn test_serialization() {
+        let token = ContinuationToken::new(0x0123_4567_89ab_cdef, 2, u64::MAX);
+        let string = token.to_string();
+
+        assert_eq!(string, "0123456789abcdef0000000000000002ffffffffffffffff");
+        assert_eq!(string.parse(), Ok(token));
+        assert_eq!(ContinuationToken::from_bytes(&token.to_bytes()), Ok(token));
+
+        assert_eq!("+123".parse::<ContinuationToken>(), Err(InvalidTokenError));
+        assert_eq!(
+            "+123456789abcdef0000000000000002ffffffffffffffff"
+                .parse::<ContinuationToken>(),
+            Err(InvalidTokenError)
+        );
+        assert_eq!(
+            ContinuationToken::from_bytes(&[0; 16]),
+            Err(InvalidTokenError)
+        );
+    }
+
+    #[test]
+    fn test_pages_with_collisions() {
+        let mut tree = HashTree::with_hasher(CollidingState::new(1, 0b1));
+        for key in 0..50u16 {
+            tree.insert(key, ());
+        }
+
+        let mut keys = Vec::new();
+        let mut iter = tree.range_hash(..);
+        loop {
+            keys.extend(iter.by_ref().take(3).map(|(&key, _)| key));
+            match iter.next_token() {
+                Some(token) => iter = tree.iter_from_token(&token),
+                None => break,
+            }
+        }
+        assert!(keys.iter().eq((&tree).into_iter().map(|(key, _)| key)));
+    }
+
+    #[test]
+    fn test_pages_with_removed_collision() {
+        let mut tree = HashTree::with_hasher(CollidingState::new(1, 0b1));
+        for key in 0..50u16 {
+            tree.insert(key, ());
+        }
+
+        let (first, token) = {
+            let mut iter = tree.range_hash(..);
+            let first: Vec<u16> =
+                iter.by_ref().take(3).map(|(&k, _)| k).collect();
+            (first, iter.next_token().unwrap())
+        };
+        tree.remove(&first[0]);
+
+        let rest: Vec<u16> =
+            tree.iter_from_token(&token).map(|(&k, _)| k).collect();
+        let skipped: Vec<u16> = (0..50)
+            .filter(|key| !first.contains(key) && !rest.contains(key))
+            .collect();
+        // One pair with the hash of the removed one is skipped, as
+        // documented, and the rest are yielded once
+        assert_eq!(skipped.len(), 1);
+        let hash = |key| tree.hasher().hash_one(key);
+        assert_eq!(hash(skipped[0]), hash(first[0]));
+        assert_eq!(first.len() + rest.len() + skipped.len(), 50);
+    }
+}
@@ -0,0 +1,349 @@
+//! Pairs are sorted by hash, so any range of hashes is a contiguous part of
+//! the tree. Top `bits` bits of a hash (its prefix) split the tree into
+//! `2^bits` such parts. With `HashTree::new_with_seed` hashes are stable,
+//! so the same key always gets into the same part.
+
+use alloc::vec::Vec;
+use core::ops::{Bound, RangeBounds, RangeInclusive};
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::bulk::IntoSorted;
+use super::cursor::Cursor;
+use super::pagination::ContinuationToken;
+use super::{HashTree, TreeNode, TreePointer};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get iterator over pairs, which key hashes are in `range`, in hash
+    /// order. Subtrees out of `range` are skipped.
+    pub fn range_hash<R>(&self, range: R) -> TreeRange<'_, K, V, A>
+    where
+        R: RangeBounds<u64>,
+    {
+        TreeRange::new(&self.root, range, self.alloc.clone())
+    }
+
+    /// Get iterator over pairs, which hashes start with top `bits` bits of
+    /// `prefix`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits > 64`, or `prefix` doesn't fit into `bits` bits.
+    pub fn iter_prefix(
+        &self,
+        prefix: u64,
+        bits: u32,
+    ) -> TreeRange<'_, K, V, A> {
+        self.range_hash(prefix_range(prefix, bits))
+    }
+
+    /// Get `2^bits` iterators, one for each prefix of `bits` bits in
+    /// ascending order. Together they yield every pair once.
+    ///
+    /// ```
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new_with_seed(1);
+    /// for key in 0..1000 {
+    ///     tree.insert(key, key);
+    /// }
+    /// let lens: Vec<usize> =
+    ///     tree.partitions(2).map(|part| part.count()).collect();
+    /// assert_eq!(lens.len(), 4);
+    /// assert_eq!(lens.iter().sum::<usize>(), 1000);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits > 64`.
+    pub fn partitions(&self, bits: u32) -> Partitions<'_, K, V, A> {
+        assert!(bits <= 64, "hash has only 64 bits");
+        Partitions {
+            root: &self.root,
+            alloc: self.alloc.clone(),
+            bits,
+            next: Some(0),
+        }
+    }
+
+    /// Split the tree into `n` trees of minimal height by hash quantiles:
+    /// each shard gets a contiguous range of hashes and about `len / n`
+    /// pairs. Shards are in hash order, and keep the hasher.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn split_into_shards(self, n: usize) -> Vec<Self>
+    where
+        S: Clone,
+    {
+        assert!(n > 0, "can't split a tree into zero shards");
+        let len = (&self).into_iter().count();
+        let mut sorted =
+            IntoSorted::new(self.root, self.alloc.clone()).peekable();
+
+        let mut shards = Vec::with_capacity(n);
+        let mut taken = 0;
+        for index in 1..=n {
+            let mut chunk = AllocVec::new_in(self.alloc.clone());
+            let end = len * index / n;
+            while taken < end {
+                chunk.push(sorted.next().unwrap());
+                taken += 1;
+            }
+            // Colliding hashes are kept in one shard, so ranges don't overlap
+            while let Some(hash) = chunk.last().map(|last| last.0) {
+                match sorted.next_if(|next| next.0 == hash) {
+                    Some(next) => chunk.push(next),
+                    None => break,
+                }
+                taken += 1;
+            }
+
+            let mut shard = HashTree::with_hasher_in(
+                self.state.clone(),
+                self.alloc.clone(),
+            );
+            shard.load_sorted(chunk.into_iter(), |_, _, _| {});
+            shards.push(shard);
+        }
+        shards
+    }
+}
+
+/// Range of hashes, which start with top `bits` bits of `prefix`.
+fn prefix_range(prefix: u64, bits: u32) -> RangeInclusive<u64> {
+    assert!(bits <= 64, "hash has only 64 bits");
+    assert!(
+        prefix.checked_shr(bits).unwrap_or(0) == 0,
+        "prefix doesn't fit into {} bits",
+        bits
+    );
+    let start = prefix.checked_shl(64 - bits).unwrap_or(0);
+    let end = start | u64::MAX.checked_shr(bits).unwrap_or(0);
+    start..=end
+}
+
+// ───── TreeRange ────────────────────────────────────────────────────────── //
+
+/// Iterator over pairs with hashes in a range, returned by
+/// `HashTree::range_hash`, `iter_prefix`, `partitions` and
+/// `iter_from_token`.
+pub struct TreeRange<'a, K, V, A: Allocator> {
+    /// Cursor, pointing to the node, which will be next in iteration, like
+    /// in `TreeIter`. Iteration is finished, when the node is after `end`.
+    cursor: Cursor<'a, K, V, A>,
+    end: Bound<u64>,
+    /// Where to resume after the last yielded pair.
+    position: ContinuationToken,
+}
+
+impl<'a, K, V, A: Allocator> TreeRange<'a, K, V, A> {
+    pub(super) fn new<R>(
+        root: &'a TreePointer<K, V, A>,
+        range: R,
+        alloc: A,
+    ) -> Self
+    where
+        R: RangeBounds<u64>,
+    {
+        let first_hash = match range.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => start.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        // Empty ranges, which end before 0, give no tokens
+        let last_hash = match range.end_bound() {
+            Bound::Included(&end) => end,
+            Bound::Excluded(&end) => end.saturating_sub(1),
+            Bound::Unbounded => u64::MAX,
+        };
+        let cursor = match first_hash {
+            Some(hash) => Cursor::lower_bound(root, hash, alloc),
+            // Range starts after `u64::MAX`, so it is empty
+            None => Cursor::ghost(root, alloc),
+        };
+        TreeRange {
+            cursor,
+            end: range.end_bound().cloned(),
+            position: ContinuationToken::new(
+                first_hash.unwrap_or(u64::MAX),
+                0,
+                last_hash,
+            ),
+        }
+    }
+
+    /// Same as `next`, but gives the whole node.
+    pub(super) fn next_node(&mut self) -> Option<&'a TreeNode<K, V, A>> {
+        let node = self.cursor.node()?;
+        if !self.is_before_end(node.hash) {
+            // All the rest nodes have greater hashes
+            return None;
+        }
+        self.cursor.move_next();
+        self.position.advance(node.hash);
+        Some(node)
+    }
+
+    /// Skip up to `count` next nodes with `hash`.
+    pub(super) fn skip_colliding(&mut self, hash: u64, count: u64) {
+        for _ in 0..count {
+            match self.cursor.node() {
+                Some(node) if node.hash == hash => self.next_node(),
+                _ => break,
+            };
+        }
+    }
+
+    /// Get token to resume iteration after the last yielded pair with
+    /// `HashTree::iter_from_token`, or None if there are no more pairs.
+    pub fn next_token(&self) -> Option<ContinuationToken> {
+        let next = self.cursor.node()?;
+        self.is_before_end(next.hash).then_some(self.position)
+    }
+
+    fn is_before_end(&self, hash: u64) -> bool {
+        match self.end {
+            Bound::Included(end) => hash <= end,
+            Bound::Excluded(end) => hash < end,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for TreeRange<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next_node()?;
+        Some((&node.key, &node.value))
+    }
+}
+
+// ───── Partitions ───────────────────────────────────────────────────────── //
+
+/// Iterator over parts of the tree by hash prefix, returned by
+/// `HashTree::partitions`.
+pub struct Partitions<'a, K, V, A: Allocator> {
+    root: &'a TreePointer<K, V, A>,
+    alloc: A,
+    bits: u32,
+    /// Prefix of the next part, None after the last one.
+    next: Option<u64>,
+}
+
+impl<'a, K, V, A: Allocator + Clone> Iterator for Partitions<'a, K, V, A> {
+    type Item = TreeRange<'a, K, V, A>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prefix = self.next?;
+        let last = u64::MAX.checked_shr(64 - self.bits).unwrap_or(0);
+        self.next = if prefix < last {
+            Some(prefix + 1)
+        } else {
+            None
+        };
+
+        let range = prefix_range(prefix, self.bits);
+        Some(TreeRange::new(self.root, range, self.alloc.clone()))
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_tree::TreeStats;
+
+    fn create_tree(len: u32) -> HashTree<u32, u32> {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..len {
+            tree.insert(key, key * 2);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_range_hash() {
+        let tree = create_tree(1000);
+        let hashes: Vec<u64> = (&tree)
+            .into_iter()
+            .map(|(key, _)| tree.hasher().hash_one(key))
+            .collect();
+        let (start, end) = (hashes[100], hashes[200]);
+
+        assert!(tree
+            .range_hash(start..end)
+            .eq((&tree).into_iter().skip(100).take(100)));
+        assert_eq!(tree.range_hash(start..=end).count(), 101);
+        assert_eq!(tree.range_hash(..end).count(), 200);
+        assert_eq!(tree.range_hash(end..start).count(), 0);
+        let after_max = (Bound::Excluded(u64::MAX), Bound::Unbounded);
+        assert_eq!(tree.range_hash(after_max).count(), 0);
+    }
+
+    #[test]
+    fn test_partitions_and_prefix() {
+        let tree = create_tree(1000);
+        for bits in [0, 1, 3, 8] {
+            let parts: Vec<Vec<_>> =
+                tree.partitions(bits).map(|part| part.collect()).collect();
+            assert_eq!(parts.len(), 1 << bits);
+            assert!(parts.iter().flatten().copied().eq((&tree).into_iter()));
+
+            for (prefix, part) in (0..).zip(&parts) {
+                assert!(tree
+                    .iter_prefix(prefix, bits)
+                    .eq(part.iter().copied()));
+                for (key, _) in part {
+                    let hash = tree.hasher().hash_one(key);
+                    assert_eq!(
+                        hash.checked_shr(64 - bits).unwrap_or(0),
+                        prefix
+                    );
+                }
+            }
+        }
+
+        let (key, _) = (&tree).into_iter().next().unwrap();
+        let hash = tree.hasher().hash_one(key);
+        assert_eq!(tree.iter_prefix(hash, 64).count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "prefix doesn't fit into 2 bits")]
+    fn test_too_long_prefix() {
+        create_tree(10).iter_prefix(4, 2);
+    }
+
+    #[test]
+    fn test_split_into_shards() {
+        for (len, n) in [(0, 3), (1, 1), (2, 5), (1000, 1), (1000, 7)] {
+            let tree = create_tree(len);
+            let pairs: Vec<(u32, u32)> =
+                (&tree).into_iter().map(|(&k, &v)| (k, v)).collect();
+            let mut shards = tree.split_into_shards(n);
+
+            assert_eq!(shards.len(), n);
+            let mut joined = Vec::new();
+            for shard in &mut shards {
+                let stats = shard.stats();
+                assert_eq!(shard.validate(), Ok(()));
+                assert_eq!(stats.height, TreeStats::optimal_height(stats.len));
+                assert!(stats.len.abs_diff(len as usize / n) <= 1);
+                joined.extend(shard.iter().map(|(&k, &v)| (k, v)));
+            }
+            assert_eq!(joined, pairs);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Raw API for callers, which already know hashes of their keys. The tree
+//! doesn't hash keys again, so a hash computed once by the tree's hasher can
+//! be reused across several operations or stages of a pipeline.
+
+use allocator_api2::alloc::Allocator;
+
+use super::{HashTree, TreePointer};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get read-only raw API of the tree, which takes precomputed hashes.
+    /// Hashes must be computed by `hasher()`, which is checked in debug
+    /// builds.
+    ///
+    /// ```
+    /// use core::hash::BuildHasher;
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new();
+    /// let hash = tree.hasher().hash_one("a");
+    ///
+    /// tree.raw_entry_mut().insert_with_hash(hash, "a", 1);
+    /// let shared = &tree;
+    /// let found = shared.raw_entry().get_with_hash(hash, |k| *k == "a");
+    /// assert_eq!(found, Some((&"a", &1)));
+    /// ```
+    pub fn raw_entry(&self) -> RawHashTreeApi<'_, K, V, S, A> {
+        RawHashTreeApi { tree: self }
+    }
+
+    /// Same as `raw_entry`, but the API can change the tree.
+    pub fn raw_entry_mut(&mut self) -> RawHashTreeApiMut<'_, K, V, S, A> {
+        RawHashTreeApiMut { tree: self }
+    }
+}
+
+// ───── RawHashTreeApi ───────────────────────────────────────────────────── //
+
+/// Lookups with precomputed hashes, returned by `HashTree::raw_entry`. A key
+/// is found by `is_match`, which is called only for keys of nodes with the
+/// same hash.
+///
+/// In debug builds with `debug-invariants` feature a miss also scans the
+/// whole tree for a key, accepted by `is_match`: if there is one, `hash` is
+/// not its hash.
+pub struct RawHashTreeApi<'a, K, V, S, A: Allocator> {
+    tree: &'a HashTree<K, V, S, A>,
+}
+
+impl<'a, K, V, S, A> RawHashTreeApi<'a, K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get stored key and its value by `hash` and `is_match`, or None if not
+    /// present.
+    pub fn get_with_hash<F>(
+        self,
+        hash: u64,
+        mut is_match: F,
+    ) -> Option<(&'a K, &'a V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        match self.tree.root.find(hash, &mut is_match) {
+            TreePointer::Empty => {
+                debug_check_miss(self.tree, &mut is_match);
+                None
+            }
+            TreePointer::NonEmpty(node) => {
+                debug_check_hash(&self.tree.state, hash, &node.key);
+                Some((&node.key, &node.value))
+            }
+        }
+    }
+}
+
+// ───── RawHashTreeApiMut ────────────────────────────────────────────────── //
+
+/// Operations with precomputed hashes, returned by `HashTree::raw_entry_mut`.
+/// Hashes are checked like in `RawHashTreeApi`.
+pub struct RawHashTreeApiMut<'a, K, V, S, A: Allocator> {
+    tree: &'a mut HashTree<K, V, S, A>,
+}
+
+impl<'a, K, V, S, A> RawHashTreeApiMut<'a, K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Same as `RawHashTreeApi::get_with_hash`.
+    pub fn get_with_hash<F>(
+        self,
+        hash: u64,
+        is_match: F,
+    ) -> Option<(&'a K, &'a V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let tree: &'a HashTree<K, V, S, A> = self.tree;
+        tree.raw_entry().get_with_hash(hash, is_match)
+    }
+
+    /// Same as `HashTree::insert`, but `key` is not hashed.
+    pub fn insert_with_hash(self, hash: u64, key: K, value: V) -> Option<V> {
+        debug_check_hash(&self.tree.state, hash, &key);
+        self.tree.insert_hashed(hash, key, value)
+    }
+
+    /// Remove pair by `hash` and `is_match`, returns stored key and value, or
+    /// None if not present.
+    pub fn remove_with_hash<F>(
+        self,
+        hash: u64,
+        mut is_match: F,
+    ) -> Option<(K, V)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let removed = self.tree.root.remove(hash, &mut is_match);
+        match removed {
+            Some((ref key, _, _)) => {
+                debug_check_hash(&self.tree.state, hash, key)
+            }
+            None => debug_check_miss(self.tree, &mut is_match),
+        }
+        self.tree.check_invariants();
+        removed.map(|(key, _, value)| (key, value))
+    }
+}
+
+/// In debug builds, panic if `hash` is not the hash of `key`: such a node
+/// would break hash order, and wouldn't be found by usual methods.
+fn debug_check_hash<K, S>(state: &S, hash: u64, key: &K)
+where
+    K: core::hash::Hash,
+    S: core::hash::BuildHasher,
+{
+    debug_assert_eq!(
+        state.hash_one(key),
+        hash,
+        "hash is not computed by the tree's hasher"
+    );
+}
+
+/// Panic if a key of `tree` is accepted by `is_match`, though it wasn't found
+/// by the hash: then the hash is wrong. Scans the whole tree, so does nothing
+/// unless `debug-invariants` feature is enabled in a debug build.
+#[inline]
+fn debug_check_miss<K, V, S, A, F>(
+    tree: &HashTree<K, V, S, A>,
+    is_match: &mut F,
+) where
+    A: Allocator + Clone,
+    F: FnMut(&K) -> bool,
+{
+    if cfg!(all(debug_assertions, feature = "debug-invariants")) {
+        assert!(
+            !tree.into_iter().any(|(key, _)| is_match(key)),
+            "hash is not computed by the tree's hasher"
+        );
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_entry() {
+        let mut tree = HashTree::new_with_seed(1);
+        let hashes: Vec<u64> =
+            (0..100u32).map(|key| tree.hasher().hash_one(key)).collect();
+        for (key, &hash) in (0..100).zip(&hashes) {
+            let raw = tree.raw_entry_mut();
+            assert_eq!(raw.insert_with_hash(hash, key, key * 2), None);
+        }
+        let raw = tree.raw_entry_mut();
+        assert_eq!(raw.insert_with_hash(hashes[5], 5, 0), Some(10));
+        assert_eq!(tree.get(&5), Some(&0));
+        assert_eq!(tree.get(&6), Some(&12));
+
+        let raw = tree.raw_entry();
+        assert_eq!(raw.get_with_hash(hashes[6], |k| *k == 6), Some((&6, &12)));
+        let raw = tree.raw_entry_mut();
+        assert_eq!(raw.get_with_hash(hashes[6], |k| *k == 1000), None);
+
+        let raw = tree.raw_entry_mut();
+        assert_eq!(raw.remove_with_hash(hashes[6], |k| *k == 6), Some((6, 12)));
+        let raw = tree.raw_entry_mut();
+        assert_eq!(raw.remove_with_hash(hashes[6], |k| *k == 6), None);
+        assert_eq!(tree.get(&6), None);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "hash is not computed by the tree's hasher")]
+    fn test_wrong_hash() {
+        let mut tree = HashTree::new_with_seed(1);
+        let hash = tree.hasher().hash_one(1u32);
+        tree.raw_entry_mut()
+            .insert_with_hash(hash.wrapping_add(1), 1u32, ());
+    }
+
+    #[test]
+    #[cfg(all(debug_assertions, feature = "debug-invariants"))]
+    #[should_panic(expected = "hash is not computed by the tree's hasher")]
+    fn test_wrong_hash_miss() {
+        let mut tree = HashTree::new_with_seed(1);
+        tree.insert(1u32, ());
+        let hash = tree.hasher().hash_one(1u32);
+        let shared = &tree;
+        shared
+            .raw_entry()
+            .get_with_hash(hash.wrapping_add(1), |k| *k == 1);
+    }
+}
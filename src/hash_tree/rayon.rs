@@ -0,0 +1,449 @@
+use std::vec::Vec;
+
+use ::rayon::iter::plumbing::{
+    bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer,
+};
+use ::rayon::iter::{
+    FromParallelIterator, IntoParallelIterator, ParallelIterator,
+};
+use ::rayon::slice::ParallelSliceMut;
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::boxed::Box;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, TreeNode, TreePointer};
+
+// ───── ParIter ──────────────────────────────────────────────────────────── //
+
+/// Parallel iterator over references to the pairs of a `HashTree`, returned
+/// by `par_iter`. Work is split at subtrees, so the order is unspecified.
+pub struct ParIter<'a, K, V, A: Allocator = Global> {
+    root: &'a TreePointer<K, V, A>,
+    alloc: &'a A,
+}
+
+/// Splits a subtree into its left subtree and its root with right subtree.
+struct IterProducer<'a, K, V, A: Allocator> {
+    first: Option<&'a TreeNode<K, V, A>>,
+    tree: &'a TreePointer<K, V, A>,
+    alloc: &'a A,
+}
+
+impl<'a, K, V, A> UnindexedProducer for IterProducer<'a, K, V, A>
+where
+    K: Sync,
+    V: Sync,
+    A: Allocator + Clone + Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        match self.tree {
+            TreePointer::Empty => (self, None),
+            TreePointer::NonEmpty(node) => (
+                IterProducer {
+                    first: self.first,
+                    tree: &node.left,
+                    alloc: self.alloc,
+                },
+                Some(IterProducer {
+                    first: Some(node.as_ref()),
+                    tree: &node.right,
+                    alloc: self.alloc,
+                }),
+            ),
+        }
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        if let Some(node) = self.first {
+            folder = folder.consume((&node.key, &node.value));
+        }
+        folder.consume_iter(self.tree.iter_in(self.alloc.clone()))
+    }
+}
+
+impl<'a, K, V, A> ParallelIterator for ParIter<'a, K, V, A>
+where
+    K: Sync,
+    V: Sync,
+    A: Allocator + Clone + Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let producer = IterProducer {
+            first: None,
+            tree: self.root,
+            alloc: self.alloc,
+        };
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+// ───── ParIterMut ───────────────────────────────────────────────────────── //
+
+/// Parallel iterator over the pairs of a `HashTree` with mutable references
+/// to values, returned by `par_iter_mut`.
+pub struct ParIterMut<'a, K, V, A: Allocator = Global> {
+    root: &'a mut TreePointer<K, V, A>,
+    alloc: &'a A,
+}
+
+struct IterMutProducer<'a, K, V, A: Allocator> {
+    first: Option<(&'a K, &'a mut V)>,
+    tree: &'a mut TreePointer<K, V, A>,
+    alloc: &'a A,
+}
+
+impl<'a, K, V, A> UnindexedProducer for IterMutProducer<'a, K, V, A>
+where
+    K: Send + Sync,
+    V: Send,
+    A: Allocator + Clone + Send + Sync,
+{
+    type Item = (&'a K, &'a mut V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        match self.tree {
+            TreePointer::Empty => (self, None),
+            TreePointer::NonEmpty(node) => {
+                let TreeNode {
+                    key,
+                    value,
+                    left,
+                    right,
+                    ..
+                } = &mut **node;
+                (
+                    IterMutProducer {
+                        first: self.first,
+                        tree: left,
+                        alloc: self.alloc,
+                    },
+                    Some(IterMutProducer {
+                        first: Some((key, value)),
+                        tree: right,
+                        alloc: self.alloc,
+                    }),
+                )
+            }
+        }
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        if let Some(pair) = self.first {
+            folder = folder.consume(pair);
+        }
+        let mut iter = TreeIterMut {
+            unvisited: AllocVec::new_in(self.alloc.clone()),
+        };
+        iter.push_left_edge(self.tree);
+        folder.consume_iter(iter)
+    }
+}
+
+impl<'a, K, V, A> ParallelIterator for ParIterMut<'a, K, V, A>
+where
+    K: Send + Sync,
+    V: Send,
+    A: Allocator + Clone + Send + Sync,
+{
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let producer = IterMutProducer {
+            first: None,
+            tree: self.root,
+            alloc: self.alloc,
+        };
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+/// Sequential in-order iteration with mutable values. Like in `TreeIter`,
+/// the stack holds unvisited nodes, but split into disjoint borrows, so
+/// right subtree can be visited after the value was given away.
+struct TreeIterMut<'a, K, V, A: Allocator> {
+    unvisited: AllocVec<UnvisitedMut<'a, K, V, A>, A>,
+}
+
+/// Key and value of a node with its right subtree.
+type UnvisitedMut<'a, K, V, A> =
+    (&'a K, &'a mut V, &'a mut TreePointer<K, V, A>);
+
+impl<'a, K, V, A: Allocator> TreeIterMut<'a, K, V, A> {
+    fn push_left_edge(&mut self, mut tree_ptr: &'a mut TreePointer<K, V, A>) {
+        while let TreePointer::NonEmpty(node) = tree_ptr {
+            let TreeNode {
+                key,
+                value,
+                left,
+                right,
+                ..
+            } = &mut **node;
+            self.unvisited.push((key, value, right));
+            tree_ptr = left;
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for TreeIterMut<'a, K, V, A> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value, right) = self.unvisited.pop()?;
+        self.push_left_edge(right);
+        Some((key, value))
+    }
+}
+
+// ───── IntoParIter ──────────────────────────────────────────────────────── //
+
+/// Parallel iterator, which moves pairs out of a `HashTree`, returned by
+/// `into_par_iter`.
+pub struct IntoParIter<K, V, A: Allocator = Global> {
+    root: TreePointer<K, V, A>,
+    alloc: A,
+}
+
+struct IntoIterProducer<K, V, A: Allocator> {
+    first: Option<(K, V)>,
+    tree: TreePointer<K, V, A>,
+    alloc: A,
+}
+
+impl<K, V, A> UnindexedProducer for IntoIterProducer<K, V, A>
+where
+    K: Send,
+    V: Send,
+    A: Allocator + Clone + Send,
+{
+    type Item = (K, V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        match self.tree {
+            TreePointer::Empty => (self, None),
+            TreePointer::NonEmpty(node) => {
+                let node = Box::into_inner(node);
+                (
+                    IntoIterProducer {
+                        first: self.first,
+                        tree: node.left,
+                        alloc: self.alloc.clone(),
+                    },
+                    Some(IntoIterProducer {
+                        first: Some((node.key, node.value)),
+                        tree: node.right,
+                        alloc: self.alloc,
+                    }),
+                )
+            }
+        }
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: Folder<Self::Item>,
+    {
+        if let Some(pair) = self.first {
+            folder = folder.consume(pair);
+        }
+        let mut unvisited = AllocVec::new_in(self.alloc);
+        unvisited.push(self.tree);
+        let nodes = core::iter::from_fn(move || loop {
+            if let TreePointer::NonEmpty(node) = unvisited.pop()? {
+                let node = Box::into_inner(node);
+                unvisited.push(node.right);
+                unvisited.push(node.left);
+                return Some((node.key, node.value));
+            }
+        });
+        folder.consume_iter(nodes)
+    }
+}
+
+impl<K, V, A> ParallelIterator for IntoParIter<K, V, A>
+where
+    K: Send,
+    V: Send,
+    A: Allocator + Clone + Send,
+{
+    type Item = (K, V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let producer = IntoIterProducer {
+            first: None,
+            tree: self.root,
+            alloc: self.alloc,
+        };
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> IntoParallelIterator for HashTree<K, V, S, A>
+where
+    K: Send,
+    V: Send,
+    A: Allocator + Clone + Send,
+{
+    type Item = (K, V);
+    type Iter = IntoParIter<K, V, A>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        IntoParIter {
+            root: self.root,
+            alloc: self.alloc,
+        }
+    }
+}
+
+impl<'a, K, V, S, A> IntoParallelIterator for &'a HashTree<K, V, S, A>
+where
+    K: Sync,
+    V: Sync,
+    A: Allocator + Clone + Sync,
+{
+    type Item = (&'a K, &'a V);
+    type Iter = ParIter<'a, K, V, A>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter {
+            root: &self.root,
+            alloc: &self.alloc,
+        }
+    }
+}
+
+impl<'a, K, V, S, A> IntoParallelIterator for &'a mut HashTree<K, V, S, A>
+where
+    K: Send + Sync,
+    V: Send,
+    A: Allocator + Clone + Send + Sync,
+{
+    type Item = (&'a K, &'a mut V);
+    type Iter = ParIterMut<'a, K, V, A>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIterMut {
+            root: &mut self.root,
+            alloc: &self.alloc,
+        }
+    }
+}
+
+/// Keys are hashed and sorted in parallel, then a tree of minimal height is
+/// built from them, like in `HashTree::bulk_load`. As with `insert`, the last
+/// value of a duplicated key wins.
+impl<K, V, S, A> FromParallelIterator<(K, V)> for HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq + Send,
+    V: Send,
+    S: core::hash::BuildHasher + Default + Sync,
+    A: Allocator + Clone + Default,
+{
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        let state = S::default();
+        let mut entries: Vec<(u64, K, V)> = par_iter
+            .into_par_iter()
+            .map(|(key, value)| (state.hash_one(&key), key, value))
+            .collect();
+        // Stable sort keeps duplicated keys in the order they came
+        entries.par_sort_by_key(|&(hash, _, _)| hash);
+
+        let mut tree = HashTree::with_hasher_in(state, A::default());
+        tree.load_sorted(entries.into_iter(), |_, old, new| *old = new);
+        tree
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use ::rayon::prelude::*;
+
+    use super::*;
+    use crate::hash_tree::TreeStats;
+
+    /// Built at once, because `debug-invariants` checks the whole tree after
+    /// every `insert`.
+    fn create_tree(len: u32) -> HashTree<u32, u64> {
+        let mut tree = HashTree::new_with_seed(1);
+        tree.bulk_load((0..len).map(|key| (key, u64::from(key))));
+        tree
+    }
+
+    #[test]
+    fn test_par_iter() {
+        let tree = create_tree(10_000);
+        let sum: u64 = tree.par_iter().map(|(_, &value)| value).sum();
+        assert_eq!(sum, (0..10_000).sum());
+        assert_eq!(tree.par_iter().count(), 10_000);
+    }
+
+    #[test]
+    fn test_par_iter_mut() {
+        let mut tree = create_tree(10_000);
+        tree.par_iter_mut().for_each(|(&key, value)| {
+            *value += u64::from(key);
+        });
+        for key in 0..10_000 {
+            assert_eq!(tree.get(&key), Some(&(u64::from(key) * 2)));
+        }
+    }
+
+    #[test]
+    fn test_into_par_iter() {
+        let tree = create_tree(10_000);
+        let mut pairs: Vec<_> = tree.into_par_iter().collect();
+        pairs.sort_unstable();
+        let expected: Vec<_> = (0..10_000).map(|k| (k, u64::from(k))).collect();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_from_par_iter() {
+        // Every key comes twice, the second value must win
+        let tree: HashTree<u32, u64> = (0..20_000u32)
+            .into_par_iter()
+            .map(|i| (i % 10_000, u64::from(i)))
+            .collect();
+
+        assert_eq!(tree.validate(), Ok(()));
+        assert_eq!(tree.par_iter().count(), 10_000);
+        for key in 0..10_000 {
+            assert_eq!(tree.get(&key), Some(&u64::from(key + 10_000)));
+        }
+
+        let stats = tree.stats();
+        assert_eq!(stats.height, TreeStats::optimal_height(10_000));
+    }
+
+    #[test]
+    fn test_empty_tree() {
+        let tree: HashTree<u32, u64> = Vec::new().into_par_iter().collect();
+        assert_eq!(tree.par_iter().count(), 0);
+        assert_eq!(tree.into_par_iter().count(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+//! Day–Stout–Warren rebalancing: the tree is rotated into a "vine" (a
+//! sorted linked list through `right` pointers), and then the vine is
+//! compressed with left rotations into a complete tree. Rotations keep the
+//! in-order sequence, so hash order is never broken.
+
+use allocator_api2::alloc::Allocator;
+
+use super::{HashTree, TreePointer, TreeStats};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Rebuild the tree into a complete tree of minimal height. Takes O(n)
+    /// time and doesn't allocate: nodes are only rotated.
+    pub fn rebalance(&mut self) {
+        let len = tree_to_vine(&mut self.root);
+        vine_to_tree(&mut self.root, len);
+        self.check_invariants();
+    }
+
+    /// Call `rebalance`, if `should_rebalance` returns `true` for current
+    /// `TreeStats`. Returns whether the tree was rebalanced.
+    ///
+    /// ```
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new();
+    /// for key in 0..1000 {
+    ///     tree.insert(key, key);
+    /// }
+    /// tree.rebalance_if(|stats| stats.imbalance_ratio > 1.5);
+    /// assert!(tree.stats().imbalance_ratio <= 1.5);
+    /// ```
+    pub fn rebalance_if<F>(&mut self, should_rebalance: F) -> bool
+    where
+        F: FnOnce(&TreeStats) -> bool,
+    {
+        let rebalance = should_rebalance(&self.stats());
+        if rebalance {
+            self.rebalance();
+        }
+        rebalance
+    }
+}
+
+// ───── Rotations ────────────────────────────────────────────────────────── //
+
+/// Make left child of `tree_ptr` its parent.
+fn rotate_right<K, V, A: Allocator>(tree_ptr: &mut TreePointer<K, V, A>) {
+    let mut node = tree_ptr.take().unwrap();
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    left.right = TreePointer::NonEmpty(node);
+    *tree_ptr = TreePointer::NonEmpty(left);
+}
+
+/// Make right child of `tree_ptr` its parent.
+fn rotate_left<K, V, A: Allocator>(tree_ptr: &mut TreePointer<K, V, A>) {
+    let mut node = tree_ptr.take().unwrap();
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    right.left = TreePointer::NonEmpty(node);
+    *tree_ptr = TreePointer::NonEmpty(right);
+}
+
+/// Rotate the tree into a vine, where every node has only a right child.
+/// Returns the number of nodes.
+pub(super) fn tree_to_vine<K, V, A: Allocator>(
+    root: &mut TreePointer<K, V, A>,
+) -> usize {
+    let mut len = 0;
+    let mut tail = root;
+    while tail.is_non_empty() {
+        if tail.as_ref().left.is_non_empty() {
+            rotate_right(tail);
+        } else {
+            len += 1;
+            tail = &mut tail.as_mut().right;
+        }
+    }
+    len
+}
+
+/// Turn the vine of `len` nodes into a complete tree. First, extra nodes of
+/// the bottom level are moved aside, then every pass halves the vine.
+pub(super) fn vine_to_tree<K, V, A: Allocator>(
+    root: &mut TreePointer<K, V, A>,
+    len: usize,
+) {
+    // Nodes, which don't fit into a perfect tree of `len` nodes
+    let perfect_len = (1 << (TreeStats::optimal_height(len + 1) - 1)) - 1;
+    let leaves = len - perfect_len;
+    compress(root, leaves);
+
+    let mut vine_len = len - leaves;
+    while vine_len > 1 {
+        vine_len /= 2;
+        compress(root, vine_len);
+    }
+}
+
+/// Rotate left every second node of the vine `count` times.
+fn compress<K, V, A: Allocator>(root: &mut TreePointer<K, V, A>, count: usize) {
+    let mut scanner = root;
+    for _ in 0..count {
+        rotate_left(scanner);
+        scanner = &mut scanner.as_mut().right;
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebalance() {
+        for len in [0, 1, 2, 3, 4, 7, 8, 100, 1000, 1023, 1024] {
+            let mut tree = HashTree::new_with_seed(1);
+            for key in 0..len {
+                tree.insert(key, key * 2);
+            }
+            tree.rebalance();
+
+            let stats = tree.stats();
+            assert_eq!(tree.validate(), Ok(()));
+            assert_eq!(stats.len, len as usize);
+            assert_eq!(stats.height, TreeStats::optimal_height(len as usize));
+            for key in 0..len {
+                assert_eq!(tree.get(&key), Some(&(key * 2)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rebalance_if() {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..1000 {
+            tree.insert(key, key);
+        }
+        assert!(tree.stats().imbalance_ratio > 1.);
+
+        assert!(!tree.rebalance_if(|stats| stats.len > 1000));
+        assert!(tree.rebalance_if(|stats| stats.imbalance_ratio > 1.));
+        assert_eq!(tree.stats().imbalance_ratio, 1.);
+        assert!(!tree.rebalance_if(|stats| stats.imbalance_ratio > 1.));
+    }
+}
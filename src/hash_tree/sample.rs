@@ -0,0 +1,221 @@
+//! Sampling by hash. Hashes look random, so pairs with the smallest hashes
+//! are a uniform sample of the tree. With a fixed seed (see
+//! `HashTree::new_with_seed`) such a sample is the same in every run, and
+//! trees with the same seed sample the same keys.
+
+use alloc::vec::Vec;
+use core::ops::Bound;
+
+use allocator_api2::alloc::Allocator;
+
+use super::{HashTree, TreeIter, TreeRange};
+
+/// Number of distinct hashes, as `f64`.
+const HASH_SPACE: f64 = 18_446_744_073_709_551_616.0;
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Get iterator over pairs with hashes in the lowest `fraction` of all
+    /// hashes, so it yields about `fraction * len` pairs. A key is in the
+    /// sample or not regardless of other keys of the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fraction` is not in `0.0..=1.0`.
+    pub fn sample_by_hash(&self, fraction: f64) -> TreeRange<'_, K, V, A> {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "fraction must be in 0.0..=1.0"
+        );
+        if fraction == 1.0 {
+            return self.range_hash(..);
+        }
+        // Cast saturates, and `fraction * HASH_SPACE` is below `2^64`
+        let threshold = (fraction * HASH_SPACE) as u64;
+        self.range_hash((Bound::Unbounded, Bound::Excluded(threshold)))
+    }
+
+    /// Get iterator over `k` pairs with the smallest hashes, or over all
+    /// pairs, if there are less than `k`.
+    pub fn sample_k(
+        &self,
+        k: usize,
+    ) -> core::iter::Take<TreeIter<'_, K, V, A>> {
+        self.into_iter().take(k)
+    }
+
+    /// Get `BottomKSketch` of keys, made of `k` smallest hashes.
+    pub fn bottom_k_sketch(&self, k: usize) -> BottomKSketch {
+        let mut hashes = Vec::with_capacity(k);
+        let mut nodes = self.range_hash(..);
+        while hashes.len() < k {
+            let hash = match nodes.next_node() {
+                Some(node) => node.hash,
+                None => break,
+            };
+            // Colliding keys are counted as one
+            if hashes.last() != Some(&hash) {
+                hashes.push(hash);
+            }
+        }
+        BottomKSketch { k, hashes }
+    }
+}
+
+// ───── BottomKSketch ────────────────────────────────────────────────────── //
+
+/// Small summary of a set of keys: `k` smallest distinct hashes of them.
+/// Estimates the number of keys and similarity of two sets, without the
+/// sets themselves. Sketches are comparable only if their trees have the
+/// same hasher.
+///
+/// ```
+/// use ghashy_collections::hash_tree::*;
+///
+/// let mut a = HashTree::new_with_seed(1);
+/// let mut b = HashTree::new_with_seed(1);
+/// for key in 0..10_000 {
+///     a.insert(key, ());
+///     b.insert(key + 5_000, ());
+/// }
+/// let (a, b) = (a.bottom_k_sketch(512), b.bottom_k_sketch(512));
+///
+/// assert!((a.estimate_cardinality() - 10_000.).abs() < 1_000.);
+/// assert!((a.jaccard(&b) - 1. / 3.).abs() < 0.1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BottomKSketch {
+    k: usize,
+    /// Sorted distinct hashes, no more than `k`.
+    hashes: Vec<u64>,
+}
+
+impl BottomKSketch {
+    /// Maximal number of hashes in the sketch.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Estimate the number of distinct keys. It is exact, if there are less
+    /// than `k` of them.
+    pub fn estimate_cardinality(&self) -> f64 {
+        match self.hashes.last() {
+            Some(&kth) if self.hashes.len() == self.k && self.k > 1 => {
+                // `k - 1` hashes are below the `k`-th smallest one
+                (self.k - 1) as f64 * HASH_SPACE / (kth as f64 + 1.0)
+            }
+            _ => self.hashes.len() as f64,
+        }
+    }
+
+    /// Estimate Jaccard similarity of two sets of keys: the size of their
+    /// intersection divided by the size of their union. Two empty sets are
+    /// equal, so their similarity is 1.
+    pub fn jaccard(&self, other: &BottomKSketch) -> f64 {
+        let k = self.k.min(other.k);
+        let (mut a, mut b) = (
+            self.hashes.iter().peekable(),
+            other.hashes.iter().peekable(),
+        );
+        let (mut union, mut both) = (0, 0);
+
+        // `k` smallest hashes of the union, and how many are in both sets
+        while union < k {
+            match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x == y => {
+                    both += 1;
+                    a.next();
+                    b.next();
+                }
+                (Some(x), Some(y)) if x < y => {
+                    a.next();
+                }
+                (Some(_), Some(_)) => {
+                    b.next();
+                }
+                (Some(_), None) => {
+                    a.next();
+                }
+                (None, Some(_)) => {
+                    b.next();
+                }
+                (None, None) => break,
+            }
+            union += 1;
+        }
+
+        if union == 0 {
+            return 1.0;
+        }
+        both as f64 / union as f64
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Built at once, because `debug-invariants` checks the whole tree after
+    /// every `insert`.
+    fn create_tree(keys: core::ops::Range<u32>) -> HashTree<u32, ()> {
+        let mut tree = HashTree::new_with_seed(1);
+        tree.bulk_load(keys.map(|key| (key, ())));
+        tree
+    }
+
+    #[test]
+    fn test_sample_by_hash() {
+        let a = create_tree(0..10_000);
+        let b = create_tree(5_000..20_000);
+
+        assert_eq!(a.sample_by_hash(0.0).count(), 0);
+        assert_eq!(a.sample_by_hash(1.0).count(), 10_000);
+        let len = a.sample_by_hash(0.1).count();
+        assert!(len.abs_diff(1_000) < 150);
+
+        // Keys of both trees are sampled in both or in none
+        let sampled_a: Vec<u32> =
+            a.sample_by_hash(0.1).map(|(&key, _)| key).collect();
+        let sampled_b: Vec<u32> =
+            b.sample_by_hash(0.1).map(|(&key, _)| key).collect();
+        for key in 5_000..10_000 {
+            assert_eq!(sampled_a.contains(&key), sampled_b.contains(&key));
+        }
+    }
+
+    #[test]
+    fn test_sample_k() {
+        let tree = create_tree(0..100);
+
+        assert!(tree.sample_k(10).eq(tree.into_iter().take(10)));
+        assert_eq!(tree.sample_k(1000).count(), 100);
+    }
+
+    #[test]
+    fn test_bottom_k_sketch() {
+        let small = create_tree(0..10).bottom_k_sketch(16);
+        assert_eq!(small.estimate_cardinality(), 10.);
+        assert_eq!(small.jaccard(&small), 1.);
+
+        let a = create_tree(0..10_000).bottom_k_sketch(256);
+        let b = create_tree(5_000..15_000).bottom_k_sketch(256);
+        let c = create_tree(20_000..30_000).bottom_k_sketch(256);
+        assert!((a.estimate_cardinality() - 10_000.).abs() < 2_000.);
+        assert!((a.jaccard(&b) - 1. / 3.).abs() < 0.1);
+        assert_eq!(a.jaccard(&c), 0.);
+        assert_eq!(a.jaccard(&a), 1.);
+
+        let empty = create_tree(0..0).bottom_k_sketch(16);
+        assert_eq!(empty.estimate_cardinality(), 0.);
+        assert_eq!(empty.jaccard(&empty), 1.);
+        assert_eq!(empty.jaccard(&a), 0.);
+    }
+}
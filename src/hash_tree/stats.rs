@@ -0,0 +1,150 @@
+use alloc::vec::Vec;
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, TreeNode, TreePointer};
+
+// ───── TreeStats ────────────────────────────────────────────────────────── //
+
+/// Snapshot of the shape of a `HashTree`, returned by `HashTree::stats`.
+///
+/// Depth of the root is 0, so finding a node at depth `d` costs `d + 1`
+/// hash comparisons.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeStats {
+    /// Number of nodes in the tree.
+    pub len: usize,
+    /// Number of levels in the tree, 0 for an empty tree.
+    pub height: usize,
+    /// Mean depth of all nodes, 0.0 for an empty tree.
+    pub average_depth: f64,
+    /// Depth of the deepest node, 0 for an empty tree.
+    pub max_depth: usize,
+    /// `depth_histogram[d]` is the number of nodes at depth `d`.
+    pub depth_histogram: Vec<usize>,
+    /// Number of nodes without children.
+    pub leaf_count: usize,
+    /// Number of nodes whose hash equals the hash of the previous node in
+    /// hash order.
+    pub colliding_hashes: usize,
+    /// Ratio of `height` to the height of a perfectly balanced tree with the
+    /// same number of nodes. 1.0 means the tree is perfectly balanced.
+    pub imbalance_ratio: f64,
+}
+
+impl TreeStats {
+    /// Height of a perfectly balanced tree with `len` nodes.
+    pub fn optimal_height(len: usize) -> usize {
+        (usize::BITS - len.leading_zeros()) as usize
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> HashTree<K, V, S, A> {
+    /// Collect `TreeStats` of this tree in one in-order traversal.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats {
+            len: 0,
+            height: 0,
+            average_depth: 0.,
+            max_depth: 0,
+            depth_histogram: Vec::new(),
+            leaf_count: 0,
+            colliding_hashes: 0,
+            imbalance_ratio: 1.,
+        };
+
+        let mut depth_sum = 0;
+        let mut previous_hash = None;
+        let mut unvisited = AllocVec::new_in(self.alloc.clone());
+        push_left_edge(&mut unvisited, &self.root, 0);
+
+        while let Some((node, depth)) = unvisited.pop() {
+            stats.len += 1;
+            depth_sum += depth;
+            if stats.depth_histogram.len() <= depth {
+                stats.depth_histogram.resize(depth + 1, 0);
+            }
+            stats.depth_histogram[depth] += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+
+            if !node.left.is_non_empty() && !node.right.is_non_empty() {
+                stats.leaf_count += 1;
+            }
+            if previous_hash == Some(node.hash) {
+                stats.colliding_hashes += 1;
+            }
+            previous_hash = Some(node.hash);
+
+            push_left_edge(&mut unvisited, &node.right, depth + 1);
+        }
+
+        if stats.len > 0 {
+            stats.height = stats.max_depth + 1;
+            stats.average_depth = depth_sum as f64 / stats.len as f64;
+            stats.imbalance_ratio = stats.height as f64
+                / TreeStats::optimal_height(stats.len) as f64;
+        }
+        stats
+    }
+}
+
+fn push_left_edge<'a, K, V, A: Allocator>(
+    unvisited: &mut AllocVec<(&'a TreeNode<K, V, A>, usize), A>,
+    mut tree_ptr: &'a TreePointer<K, V, A>,
+    mut depth: usize,
+) {
+    while let TreePointer::NonEmpty(ref node) = *tree_ptr {
+        unvisited.push((node.as_ref(), depth));
+        tree_ptr = &node.left;
+        depth += 1;
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_of_empty_tree() {
+        let tree: HashTree<u32, u32> = HashTree::new();
+        let stats = tree.stats();
+
+        assert_eq!(stats.len, 0);
+        assert_eq!(stats.height, 0);
+        assert_eq!(stats.leaf_count, 0);
+        assert!(stats.depth_histogram.is_empty());
+        assert_eq!(stats.imbalance_ratio, 1.);
+    }
+
+    #[test]
+    fn test_stats_shape() {
+        // Same tree as in `test_hash_tree_creation`:
+        //
+        //       1
+        //      / \
+        //     2   3
+        //          \
+        //           4
+        //          /
+        //         5
+        let mut tree: HashTree<u32, f32> = HashTree::new_with_seed(1);
+        tree.insert(1, 10.);
+        tree.insert(2, 20.);
+        tree.insert(3, 30.);
+        tree.insert(4, 40.);
+        tree.insert(5, 50.);
+
+        let stats = tree.stats();
+        assert_eq!(stats.len, 5);
+        assert_eq!(stats.height, 4);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.depth_histogram, vec![1, 2, 1, 1]);
+        assert_eq!(stats.leaf_count, 2);
+        assert_eq!(stats.colliding_hashes, 0);
+        assert_eq!(stats.average_depth, 7. / 5.);
+        assert_eq!(stats.imbalance_ratio, 4. / 3.);
+    }
+}
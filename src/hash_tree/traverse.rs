@@ -0,0 +1,260 @@
+//! Traversals, which follow the shape of the tree instead of hash order:
+//! pre-order, post-order and level-order. Every pair comes with its place in
+//! the tree, so the shape can be drawn, or saved and rebuilt.
+
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, TreeNode, TreePointer};
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A: Allocator + Clone> HashTree<K, V, S, A> {
+    /// Get iterator over nodes of the tree in `order`.
+    ///
+    /// ```
+    /// use ghashy_collections::hash_tree::*;
+    ///
+    /// let mut tree = HashTree::new();
+    /// for key in 0..10 {
+    ///     tree.insert(key, key);
+    /// }
+    /// let mut levels = Vec::new();
+    /// for node in tree.traverse(TraversalOrder::LevelOrder) {
+    ///     if levels.len() == node.depth {
+    ///         levels.push(Vec::new());
+    ///     }
+    ///     levels[node.depth].push(*node.key);
+    /// }
+    /// assert_eq!(levels[0].len(), 1);
+    /// ```
+    pub fn traverse(&self, order: TraversalOrder) -> Traverse<'_, K, V, A> {
+        let mut traverse = Traverse {
+            order,
+            unvisited: AllocVec::new_in(self.alloc.clone()),
+            next_level: AllocVec::new_in(self.alloc.clone()),
+        };
+        traverse.push(&self.root, 0);
+        traverse
+    }
+}
+
+// ───── Traverse ─────────────────────────────────────────────────────────── //
+
+/// Order of `HashTree::traverse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraversalOrder {
+    /// Node, then its left subtree, then its right subtree.
+    PreOrder,
+    /// Left subtree, then right subtree, then the node.
+    PostOrder,
+    /// Level by level from the root, each level from left to right.
+    LevelOrder,
+}
+
+/// Node of a `HashTree`, yielded by `HashTree::traverse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeView<'a, K, V> {
+    /// Key of the node.
+    pub key: &'a K,
+    /// Value of the node.
+    pub value: &'a V,
+    /// Hash of the key.
+    pub hash: u64,
+    /// Depth of the node, 0 for the root.
+    pub depth: usize,
+    /// Whether the node has a left child.
+    pub has_left: bool,
+    /// Whether the node has a right child.
+    pub has_right: bool,
+}
+
+/// Nodes with their depths, and whether their children are pushed.
+type Unvisited<'a, K, V, A> = AllocVec<(&'a TreeNode<K, V, A>, usize, bool), A>;
+
+/// Iterator over nodes in `TraversalOrder`, returned by `HashTree::traverse`.
+pub struct Traverse<'a, K, V, A: Allocator> {
+    order: TraversalOrder,
+    /// Stack of nodes with their depths. In post-order a node is pushed
+    /// again with `true`, when its children are pushed above it. In
+    /// level-order it is the rest of the current level, the leftmost on top.
+    unvisited: Unvisited<'a, K, V, A>,
+    /// Children of visited nodes of the current level, only for level-order.
+    next_level: Unvisited<'a, K, V, A>,
+}
+
+impl<'a, K, V, A: Allocator> Traverse<'a, K, V, A> {
+    fn push(&mut self, tree_ptr: &'a TreePointer<K, V, A>, depth: usize) {
+        if let TreePointer::NonEmpty(ref node) = *tree_ptr {
+            self.unvisited.push((node.as_ref(), depth, false));
+        }
+    }
+
+    fn push_next_level(&mut self, node: &'a TreeNode<K, V, A>, depth: usize) {
+        for child in [&node.left, &node.right] {
+            if let TreePointer::NonEmpty(ref child) = *child {
+                self.next_level.push((child.as_ref(), depth + 1, false));
+            }
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for Traverse<'a, K, V, A> {
+    type Item = NodeView<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = match self.order {
+            TraversalOrder::PreOrder => {
+                let (node, depth, _) = self.unvisited.pop()?;
+                // Left child is on top, so it is visited first
+                self.push(&node.right, depth + 1);
+                self.push(&node.left, depth + 1);
+                (node, depth)
+            }
+            TraversalOrder::PostOrder => loop {
+                let (node, depth, expanded) = self.unvisited.pop()?;
+                if expanded {
+                    break (node, depth);
+                }
+                self.unvisited.push((node, depth, true));
+                self.push(&node.right, depth + 1);
+                self.push(&node.left, depth + 1);
+            },
+            TraversalOrder::LevelOrder => {
+                if self.unvisited.is_empty() {
+                    core::mem::swap(&mut self.unvisited, &mut self.next_level);
+                    self.unvisited.reverse();
+                }
+                let (node, depth, _) = self.unvisited.pop()?;
+                self.push_next_level(node, depth);
+                (node, depth)
+            }
+        };
+        Some(NodeView {
+            key: &node.key,
+            value: &node.value,
+            hash: node.hash,
+            depth,
+            has_left: node.left.is_non_empty(),
+            has_right: node.right.is_non_empty(),
+        })
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shape of a tree, rebuilt from its pre-order traversal.
+    struct Shape {
+        key: u32,
+        depth: usize,
+        left: Option<Box<Shape>>,
+        right: Option<Box<Shape>>,
+    }
+
+    impl Shape {
+        fn from_pre_order<'a, I>(nodes: &mut I) -> Shape
+        where
+            I: Iterator<Item = NodeView<'a, u32, u32>>,
+        {
+            let node = nodes.next().unwrap();
+            let left = node
+                .has_left
+                .then(|| Box::new(Shape::from_pre_order(nodes)));
+            let right = node
+                .has_right
+                .then(|| Box::new(Shape::from_pre_order(nodes)));
+            Shape {
+                key: *node.key,
+                depth: node.depth,
+                left,
+                right,
+            }
+        }
+
+        fn children(&self) -> impl Iterator<Item = &Shape> {
+            self.left
+                .iter()
+                .chain(self.right.iter())
+                .map(|c| c.as_ref())
+        }
+
+        fn in_order(&self, keys: &mut Vec<u32>) {
+            self.left.iter().for_each(|left| left.in_order(keys));
+            keys.push(self.key);
+            self.right.iter().for_each(|right| right.in_order(keys));
+        }
+
+        fn post_order(&self, keys: &mut Vec<u32>) {
+            self.children().for_each(|child| child.post_order(keys));
+            keys.push(self.key);
+        }
+
+        fn level_order(&self) -> Vec<u32> {
+            let mut keys = Vec::new();
+            let mut level = vec![self];
+            while !level.is_empty() {
+                keys.extend(level.iter().map(|shape| shape.key));
+                level =
+                    level.iter().flat_map(|shape| shape.children()).collect();
+            }
+            keys
+        }
+
+        fn check_depths(&self, depth: usize) {
+            assert_eq!(self.depth, depth);
+            self.children()
+                .for_each(|child| child.check_depths(depth + 1));
+        }
+    }
+
+    fn keys(tree: &HashTree<u32, u32>, order: TraversalOrder) -> Vec<u32> {
+        tree.traverse(order).map(|node| *node.key).collect()
+    }
+
+    #[test]
+    fn test_traverse() {
+        let mut tree = HashTree::new_with_seed(1);
+        for key in 0..100 {
+            tree.insert(key, key);
+        }
+
+        let mut pre_order = tree.traverse(TraversalOrder::PreOrder);
+        let shape = Shape::from_pre_order(&mut pre_order);
+        assert!(pre_order.next().is_none());
+        shape.check_depths(0);
+
+        let mut in_order = Vec::new();
+        shape.in_order(&mut in_order);
+        let expected: Vec<u32> = (&tree).into_iter().map(|(&k, _)| k).collect();
+        assert_eq!(in_order, expected);
+
+        let mut post_order = Vec::new();
+        shape.post_order(&mut post_order);
+        assert_eq!(keys(&tree, TraversalOrder::PostOrder), post_order);
+        assert_eq!(
+            keys(&tree, TraversalOrder::LevelOrder),
+            shape.level_order()
+        );
+
+        for node in tree.traverse(TraversalOrder::LevelOrder) {
+            assert_eq!(node.value, node.key);
+            assert_eq!(node.hash, tree.state.hash_one(node.key));
+        }
+    }
+
+    #[test]
+    fn test_traverse_empty() {
+        let tree: HashTree<u32, u32> = HashTree::new();
+        for order in [
+            TraversalOrder::PreOrder,
+            TraversalOrder::PostOrder,
+            TraversalOrder::LevelOrder,
+        ] {
+            assert_eq!(tree.traverse(order).count(), 0);
+        }
+    }
+}
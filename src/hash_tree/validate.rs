@@ -0,0 +1,164 @@
+use allocator_api2::alloc::Allocator;
+use allocator_api2::vec::Vec as AllocVec;
+
+use super::{HashTree, TreeNode, TreePointer};
+use crate::error::InvariantError;
+
+// ───── HashTree ─────────────────────────────────────────────────────────── //
+
+impl<K, V, S, A> HashTree<K, V, S, A>
+where
+    K: core::hash::Hash + Eq,
+    S: core::hash::BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Check that the tree is well formed: hashes don't decrease in in-order
+    /// traversal, every stored hash is the hash of its key, and every key is
+    /// stored once.
+    pub fn validate(&self) -> Result<(), InvariantError> {
+        self.root.validate(&self.state, self.alloc.clone())
+    }
+
+    /// Panic if the tree is broken. Does nothing unless `debug-invariants`
+    /// feature is enabled in a debug build.
+    #[inline]
+    pub(super) fn check_invariants(&self) {
+        #[cfg(all(debug_assertions, feature = "debug-invariants"))]
+        if let Err(error) = self.validate() {
+            panic!("HashTree invariant is violated: {}", error);
+        }
+    }
+}
+
+// ───── TreePointer ──────────────────────────────────────────────────────── //
+
+impl<K, V, A> TreePointer<K, V, A>
+where
+    K: core::hash::Hash + Eq,
+    A: Allocator + Clone,
+{
+    /// Same as `HashTree::validate`, for the tree under this pointer.
+    pub(super) fn validate<S>(
+        &self,
+        state: &S,
+        alloc: A,
+    ) -> Result<(), InvariantError>
+    where
+        S: core::hash::BuildHasher,
+    {
+        let mut previous_hash = None;
+        // Keys of the nodes, which have the same hash as the current one.
+        // Without collisions there is only one.
+        let mut colliding_keys: AllocVec<&K, A> =
+            AllocVec::new_in(alloc.clone());
+        let mut unvisited = AllocVec::new_in(alloc);
+        push_left_edge(&mut unvisited, self);
+
+        while let Some(node) = unvisited.pop() {
+            let expected = state.hash_one(&node.key);
+            if node.hash != expected {
+                return Err(InvariantError::WrongHash {
+                    stored: node.hash,
+                    expected,
+                });
+            }
+
+            match previous_hash {
+                Some(previous) if previous > node.hash => {
+                    return Err(InvariantError::UnorderedHash {
+                        previous,
+                        hash: node.hash,
+                    });
+                }
+                Some(previous) if previous == node.hash => {
+                    if colliding_keys.contains(&&node.key) {
+                        return Err(InvariantError::DuplicateKey {
+                            hash: node.hash,
+                        });
+                    }
+                }
+                _ => colliding_keys.clear(),
+            }
+            colliding_keys.push(&node.key);
+            previous_hash = Some(node.hash);
+
+            push_left_edge(&mut unvisited, &node.right);
+        }
+        Ok(())
+    }
+}
+
+fn push_left_edge<'a, K, V, A: Allocator>(
+    unvisited: &mut AllocVec<&'a TreeNode<K, V, A>, A>,
+    mut tree_ptr: &'a TreePointer<K, V, A>,
+) {
+    while let TreePointer::NonEmpty(ref node) = *tree_ptr {
+        unvisited.push(node.as_ref());
+        tree_ptr = &node.left;
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[cfg(test)]
+mod tests {
+    use allocator_api2::alloc::Global;
+
+    use super::*;
+
+    fn create_tree() -> HashTree<u32, f32> {
+        let mut tree = HashTree::new_with_seed(1);
+        tree.insert(1, 10.);
+        tree.insert(2, 20.);
+        tree.insert(3, 30.);
+        tree.insert(4, 40.);
+        tree.insert(5, 50.);
+        tree
+    }
+
+    #[test]
+    fn test_validate_after_removing() {
+        let mut tree = create_tree();
+        assert_eq!(tree.validate(), Ok(()));
+
+        // Root has two children
+        tree.remove(&1);
+        assert_eq!(tree.validate(), Ok(()));
+        tree.remove(&4);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_finds_broken_tree() {
+        let mut tree = create_tree();
+        let expected = tree.root.as_ref().hash;
+        tree.root.as_mut().hash = 0;
+        assert_eq!(
+            tree.validate(),
+            Err(InvariantError::WrongHash {
+                stored: 0,
+                expected
+            })
+        );
+
+        let mut tree = create_tree();
+        let root = &mut **tree.root.as_mut();
+        std::mem::swap(&mut root.left, &mut root.right);
+        assert!(matches!(
+            tree.validate(),
+            Err(InvariantError::UnorderedHash { .. })
+        ));
+
+        let mut tree = create_tree();
+        let hash = tree.state.hash_one(1);
+        tree.root.as_mut().left = TreePointer::new(
+            TreePointer::Empty,
+            1,
+            hash,
+            10.,
+            TreePointer::Empty,
+            Global,
+        );
+        assert_eq!(tree.validate(), Err(InvariantError::DuplicateKey { hash }));
+    }
+}
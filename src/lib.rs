@@ -13,8 +13,17 @@
 //! assert_eq!(tree["Key"], "Value");
 //!
 //! ```
+//!
+//! The crate is `no_std` compatible: disable default `std` feature and only
+//! `alloc` is required.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 // ───── Submodules ───────────────────────────────────────────────────────── //
 
+/// Error types, shared by fallible operations of the collections.
+pub mod error;
 /// This is a module with `HashTree` related code.
 pub mod hash_tree;
@@ -24,6 +24,18 @@ fn benchmark() {
 
     println!("HashTree: {} microseconds", now.elapsed().as_micros());
 
+    // IndexedHashTree
+    now = std::time::Instant::now();
+    let mut tree = IndexedHashTree::new();
+    for index in 0..size {
+        tree.insert(index, index + 1);
+    }
+    let _ = tree.get(&99999);
+    println!(
+        "IndexedHashTree: {} microseconds",
+        now.elapsed().as_micros()
+    );
+
     // BTreeMap
     now = std::time::Instant::now();
     let mut tree = BTreeMap::new();
@@ -54,6 +66,35 @@ fn benchmark() {
         "HashMap with ahash: {} microseconds",
         now.elapsed().as_micros()
     );
+
+    println!("\nLookups: \n");
+    benchmark_lookups();
+}
+
+fn benchmark_lookups() {
+    let size = 1000000;
+    let tree: HashTree<u64, u64> = (0..size).map(|i| (i, i + 1)).collect();
+    let mut indexed = IndexedHashTree::new();
+    for index in 0..size {
+        indexed.insert(index, index + 1);
+    }
+
+    // HashTree
+    let now = std::time::Instant::now();
+    let found = (0..size).filter(|index| tree.contains_key(index)).count();
+    assert_eq!(found as u64, size);
+    println!("HashTree: {} microseconds", now.elapsed().as_micros());
+
+    // IndexedHashTree
+    let now = std::time::Instant::now();
+    let found = (0..size)
+        .filter(|index| indexed.contains_key(index))
+        .count();
+    assert_eq!(found as u64, size);
+    println!(
+        "IndexedHashTree: {} microseconds",
+        now.elapsed().as_micros()
+    );
 }
 
 fn test_visually() {
@@ -89,5 +130,5 @@ fn test_visually() {
 
     tree.remove("droid");
 
-    dbg!(tree);
+    tree.pretty_print();
 }
@@ -0,0 +1,183 @@
+//! Checks that a `HashTree` with a custom allocator never touches the global
+//! allocator. Lives in its own test binary, because it replaces the global
+//! allocator.
+
+#![allow(unsafe_code)]
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+use std::ptr::NonNull;
+
+use allocator_api2::alloc::{AllocError, Allocator, System};
+use ghashy_collections::error::TryReserveError;
+use ghashy_collections::hash_tree::{AugmentedHashTree, HashTree, Sum};
+
+// ───── Counting allocators ──────────────────────────────────────────────── //
+
+thread_local! {
+    // Counted per thread, so other tests can't interfere.
+    static GLOBAL_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Global allocator, which counts allocations made by the current thread.
+struct CountingGlobal;
+
+unsafe impl GlobalAlloc for CountingGlobal {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = GLOBAL_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingGlobal = CountingGlobal;
+
+fn global_allocations() -> usize {
+    GLOBAL_ALLOCATIONS.with(Cell::get)
+}
+
+/// `Allocator`, which counts its allocations. It bypasses the global
+/// allocator, so it's not counted twice.
+#[derive(Clone, Copy)]
+struct CountingAllocator<'a> {
+    allocated: &'a Cell<usize>,
+    live: &'a Cell<usize>,
+}
+
+unsafe impl Allocator for CountingAllocator<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocated.set(self.allocated.get() + 1);
+        self.live.set(self.live.get() + 1);
+        System.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live.set(self.live.get() - 1);
+        System.deallocate(ptr, layout)
+    }
+}
+
+/// `Allocator`, which fails after `budget` allocations.
+#[derive(Clone, Copy)]
+struct LimitedAllocator<'a> {
+    budget: &'a Cell<usize>,
+}
+
+unsafe impl Allocator for LimitedAllocator<'_> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        match self.budget.get() {
+            0 => Err(AllocError),
+            budget => {
+                self.budget.set(budget - 1);
+                System.allocate(layout)
+            }
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        System.deallocate(ptr, layout)
+    }
+}
+
+// ───── Tests ────────────────────────────────────────────────────────────── //
+
+#[test]
+fn test_no_global_allocations() {
+    let allocated = Cell::new(0);
+    let live = Cell::new(0);
+    let alloc = CountingAllocator {
+        allocated: &allocated,
+        live: &live,
+    };
+    let state = ahash::RandomState::with_seeds(1, 2, 3, 4);
+
+    let before = global_allocations();
+    {
+        let mut tree = HashTree::with_hasher_in(state, alloc);
+        for key in 0..100u32 {
+            assert_eq!(tree.insert(key, key * 2), None);
+        }
+        assert_eq!(live.get(), 100);
+
+        for key in 0..100u32 {
+            assert_eq!(tree.get(&key), Some(&(key * 2)));
+        }
+        assert_eq!(tree.iter().count(), 100);
+        assert_eq!((&tree).into_iter().count(), 100);
+
+        for key in (0..100u32).step_by(2) {
+            assert_eq!(tree.remove(&key), Some(key * 2));
+        }
+        assert_eq!(live.get(), 50);
+        assert_eq!(tree.validate(), Ok(()));
+
+        let mut other = tree.clone();
+        other.insert(1000, 0);
+        other.insert(1, 0);
+        assert_eq!(tree.diff(&other).count(), 2);
+    }
+    assert_eq!(global_allocations(), before);
+
+    // Nodes and iterator stacks all came from `alloc`, and all were freed
+    assert!(allocated.get() > 100);
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+fn test_augmented_no_global_allocations() {
+    let allocated = Cell::new(0);
+    let live = Cell::new(0);
+    let alloc = CountingAllocator {
+        allocated: &allocated,
+        live: &live,
+    };
+    let state = ahash::RandomState::with_seeds(1, 2, 3, 4);
+
+    let before = global_allocations();
+    {
+        let mut tree: AugmentedHashTree<u32, u64, Sum, _, _> =
+            AugmentedHashTree::with_hasher_in(state, alloc);
+        for key in 0..100u32 {
+            assert_eq!(tree.insert(key, 1), None);
+        }
+        assert_eq!(live.get(), 100);
+        for key in (0..100u32).step_by(2) {
+            assert_eq!(tree.remove(&key), Some(1));
+        }
+        assert_eq!(live.get(), 50);
+        assert_eq!(tree.summary(), 50);
+        assert_eq!(tree.iter().count(), 50);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+    assert_eq!(global_allocations(), before);
+    assert_eq!(live.get(), 0);
+}
+
+#[test]
+fn test_allocation_failure() {
+    let budget = Cell::new(usize::MAX);
+    let alloc = LimitedAllocator { budget: &budget };
+    let state = ahash::RandomState::with_seeds(1, 2, 3, 4);
+    let mut tree = HashTree::with_hasher_in(state, alloc);
+    for key in 0..3u32 {
+        assert_eq!(tree.try_insert_alloc(key, key), Ok(None));
+    }
+
+    budget.set(0);
+    let error: TryReserveError = tree.try_insert_alloc(3, 3).unwrap_err();
+    assert!(error.layout().size() > 0);
+
+    // Failed insertion leaves the tree unchanged. Iteration and `validate`
+    // need memory for their stacks
+    budget.set(usize::MAX);
+    let pairs: Vec<(u32, u32)> = tree.iter().map(|(&k, &v)| (k, v)).collect();
+    assert_eq!(pairs.len(), 3);
+    assert!(pairs.iter().all(|&(key, value)| key < 3 && value == key));
+    assert_eq!(tree.get(&3), None);
+    assert_eq!(tree.try_insert_alloc(0, 10), Ok(Some(0)));
+    assert_eq!(tree.validate(), Ok(()));
+}